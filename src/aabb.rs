@@ -0,0 +1,112 @@
+use crate::{interval::Interval, ray::Ray, vec3::Point};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Aabb {
+        Aabb { x, y, z }.pad_to_minimums()
+    }
+
+    pub fn from_points(a: Point, b: Point) -> Aabb {
+        Aabb::new(
+            Interval::new(a.x().min(b.x()), a.x().max(b.x())),
+            Interval::new(a.y().min(b.y()), a.y().max(b.y())),
+            Interval::new(a.z().min(b.z()), a.z().max(b.z())),
+        )
+    }
+
+    pub fn surrounding(a: Aabb, b: Aabb) -> Aabb {
+        Aabb {
+            x: Interval::surrounding(a.x, b.x),
+            y: Interval::surrounding(a.y, b.y),
+            z: Interval::surrounding(a.z, b.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> Interval {
+        match n {
+            1 => self.y,
+            2 => self.z,
+            _ => self.x,
+        }
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() {
+                0
+            } else {
+                2
+            }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn centroid(&self) -> Point {
+        Point::new(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        2. * (dx * dy + dy * dz + dz * dx)
+    }
+
+    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> bool {
+        let mut ray_t = ray_t;
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1. / ray.dir[axis];
+
+            let t0 = (ax.min - ray.origin[axis]) * adinv;
+            let t1 = (ax.max - ray.origin[axis]) * adinv;
+
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if t0 > ray_t.min {
+                ray_t.min = t0;
+            }
+            if t1 < ray_t.max {
+                ray_t.max = t1;
+            }
+
+            if ray_t.max <= ray_t.min {
+                return false;
+            }
+        }
+        true
+    }
+
+    // flat primitives get a sliver of thickness so the slab test still hits them
+    fn pad_to_minimums(self) -> Aabb {
+        const DELTA: f64 = 0.0001;
+        let pad = |i: Interval| {
+            if i.size() < DELTA {
+                i.expand(DELTA)
+            } else {
+                i
+            }
+        };
+        Aabb {
+            x: pad(self.x),
+            y: pad(self.y),
+            z: pad(self.z),
+        }
+    }
+
+    pub const EMPTY: Aabb = Aabb {
+        x: Interval::EMPTY,
+        y: Interval::EMPTY,
+        z: Interval::EMPTY,
+    };
+}
@@ -0,0 +1,226 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, HittableKind, HittableList},
+    interval::Interval,
+    ray::Ray,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum)]
+pub enum SplitStrategy {
+    Midpoint,
+    EqualCounts,
+    Sah,
+}
+
+pub enum BvhNode {
+    Leaf {
+        bbox: Aabb,
+        objects: Vec<HittableKind>,
+    },
+    Branch {
+        bbox: Aabb,
+        left: Box<BvhNode>,
+        right: Box<BvhNode>,
+    },
+}
+
+const MAX_LEAF_SIZE: usize = 2;
+const SAH_MAX_LEAF_SIZE: usize = 4;
+const SAH_BUCKETS: usize = 12;
+const SAH_TRAVERSAL_COST: f64 = 1.;
+
+impl BvhNode {
+    pub fn new(list: HittableList, strategy: SplitStrategy) -> BvhNode {
+        // unbounded objects such as planes would wreck the split heuristics,
+        // they get a leaf of their own next to the tree of everything else
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = list
+            .objects
+            .into_iter()
+            .partition(|obj| obj.bounding_box().surface_area().is_finite());
+
+        let tree = Self::build(bounded, strategy);
+        if unbounded.is_empty() {
+            return tree;
+        }
+
+        let bbox = unbounded.iter().fold(Aabb::EMPTY, |bbox, obj| {
+            Aabb::surrounding(bbox, obj.bounding_box())
+        });
+        let leaf = BvhNode::Leaf {
+            bbox,
+            objects: unbounded,
+        };
+        BvhNode::Branch {
+            bbox: Aabb::surrounding(leaf.bounding_box(), tree.bounding_box()),
+            left: Box::new(leaf),
+            right: Box::new(tree),
+        }
+    }
+
+    fn build(mut objects: Vec<HittableKind>, strategy: SplitStrategy) -> BvhNode {
+        let bbox = objects.iter().fold(Aabb::EMPTY, |bbox, obj| {
+            Aabb::surrounding(bbox, obj.bounding_box())
+        });
+        let centroid_bounds = objects.iter().fold(Aabb::EMPTY, |bounds, obj| {
+            let c = obj.bounding_box().centroid();
+            Aabb::surrounding(bounds, Aabb::from_points(c, c))
+        });
+        let axis = centroid_bounds.longest_axis();
+
+        if objects.len() <= MAX_LEAF_SIZE {
+            return BvhNode::Leaf { bbox, objects };
+        }
+
+        let mid = match strategy {
+            SplitStrategy::Midpoint => {
+                let midpoint = centroid(&centroid_bounds, axis);
+                partition(&mut objects, |obj| {
+                    centroid(&obj.bounding_box(), axis) < midpoint
+                })
+            }
+            SplitStrategy::EqualCounts => 0,
+            SplitStrategy::Sah => {
+                let split = sah_split(&objects, &bbox, &centroid_bounds, axis);
+                let leaf_cost = objects.len() as f64;
+                match split {
+                    Some((split, cost))
+                        if cost < leaf_cost || objects.len() > SAH_MAX_LEAF_SIZE =>
+                    {
+                        partition(&mut objects, |obj| {
+                            bucket(&centroid_bounds, axis, obj) <= split
+                        })
+                    }
+                    _ if objects.len() <= SAH_MAX_LEAF_SIZE => {
+                        return BvhNode::Leaf { bbox, objects };
+                    }
+                    _ => 0,
+                }
+            }
+        };
+
+        // degenerate splits fall back to halving the sorted objects
+        let mid = if mid == 0 || mid == objects.len() {
+            let mid = objects.len() / 2;
+            objects.select_nth_unstable_by(mid, |a, b| {
+                centroid(&a.bounding_box(), axis).total_cmp(&centroid(&b.bounding_box(), axis))
+            });
+            mid
+        } else {
+            mid
+        };
+
+        let right = objects.split_off(mid);
+        BvhNode::Branch {
+            bbox,
+            left: Box::new(Self::build(objects, strategy)),
+            right: Box::new(Self::build(right, strategy)),
+        }
+    }
+}
+
+fn centroid(bbox: &Aabb, axis: usize) -> f64 {
+    let interval = bbox.axis_interval(axis);
+    0.5 * (interval.min + interval.max)
+}
+
+fn bucket(centroid_bounds: &Aabb, axis: usize, obj: &HittableKind) -> usize {
+    let extent = centroid_bounds.axis_interval(axis);
+    let offset = (centroid(&obj.bounding_box(), axis) - extent.min) / extent.size();
+    ((offset * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+}
+
+// returns the last bucket on the left side of the cheapest split and its cost
+fn sah_split(
+    objects: &[HittableKind],
+    bbox: &Aabb,
+    centroid_bounds: &Aabb,
+    axis: usize,
+) -> Option<(usize, f64)> {
+    if centroid_bounds.axis_interval(axis).size() <= 0. {
+        return None;
+    }
+
+    let mut counts = [0usize; SAH_BUCKETS];
+    let mut bounds = [Aabb::EMPTY; SAH_BUCKETS];
+    for obj in objects {
+        let b = bucket(centroid_bounds, axis, obj);
+        counts[b] += 1;
+        bounds[b] = Aabb::surrounding(bounds[b], obj.bounding_box());
+    }
+
+    let mut best: Option<(usize, f64)> = None;
+    for split in 0..SAH_BUCKETS - 1 {
+        let (mut left, mut right) = (Aabb::EMPTY, Aabb::EMPTY);
+        let (mut left_count, mut right_count) = (0, 0);
+        for b in 0..=split {
+            left = Aabb::surrounding(left, bounds[b]);
+            left_count += counts[b];
+        }
+        for b in split + 1..SAH_BUCKETS {
+            right = Aabb::surrounding(right, bounds[b]);
+            right_count += counts[b];
+        }
+        if left_count == 0 || right_count == 0 {
+            continue;
+        }
+
+        let cost = SAH_TRAVERSAL_COST
+            + (left.surface_area() * left_count as f64 + right.surface_area() * right_count as f64)
+                / bbox.surface_area();
+        if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+            best = Some((split, cost));
+        }
+    }
+
+    best
+}
+
+fn partition(objects: &mut [HittableKind], pred: impl Fn(&HittableKind) -> bool) -> usize {
+    let mut first = 0;
+    for i in 0..objects.len() {
+        if pred(&objects[i]) {
+            objects.swap(first, i);
+            first += 1;
+        }
+    }
+    first
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        match self {
+            BvhNode::Leaf { bbox, objects } => {
+                if !bbox.hit(ray, ray_t) {
+                    return None;
+                }
+
+                let mut current_hit = None;
+                let mut interval = ray_t;
+                for obj in objects {
+                    if let Some(hit_rec) = obj.hit(ray, interval) {
+                        interval.max = hit_rec.t;
+                        current_hit = Some(hit_rec);
+                    }
+                }
+                current_hit
+            }
+            BvhNode::Branch { bbox, left, right } => {
+                if !bbox.hit(ray, ray_t) {
+                    return None;
+                }
+
+                let hit_left = left.hit(ray, ray_t);
+                let max = hit_left.map_or(ray_t.max, |rec| rec.t);
+                let hit_right = right.hit(ray, Interval::new(ray_t.min, max));
+
+                hit_right.or(hit_left)
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            BvhNode::Leaf { bbox, .. } | BvhNode::Branch { bbox, .. } => *bbox,
+        }
+    }
+}
@@ -1,11 +1,22 @@
-use std::thread;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
 
 use crate::{
-    hittable::{Hittable, HittableList},
+    environment::Environment,
+    hittable::{HitRecord, Hittable, HittableList},
     interval::Interval,
-    material::Material,
-    random::random_f64,
+    light::{Light, LightKind},
+    material::{Material, MaterialKind, Scatter},
+    medium::{Interaction, Medium},
+    output::Image,
+    random::{random_f64, random_f64_between},
     ray::Ray,
+    spectrum,
     vec3::{Color, Point, Vec3},
 };
 
@@ -23,12 +34,17 @@ pub struct Camera {
     defocus_angle: f64,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
+    shutter_open: f64,
+    shutter_close: f64,
+    spectral: bool,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn init(
         aspect_ratio: f64,
         image_width: i32,
+        image_height: Option<i32>,
         samples_per_pixel: u32,
         max_depth: u32,
         vfov: f64,
@@ -37,12 +53,17 @@ impl Camera {
         vup: Vec3,
         defocus_angle: f64,
         focus_dist: f64,
+        shutter_open: f64,
+        shutter_close: f64,
+        spectral: bool,
     ) -> Camera {
-        let image_height = if image_width as f64 / aspect_ratio < 1. {
-            1
-        } else {
-            (image_width as f64 / aspect_ratio) as i32
-        };
+        let image_height = image_height.unwrap_or_else(|| {
+            if image_width as f64 / aspect_ratio < 1. {
+                1
+            } else {
+                (image_width as f64 / aspect_ratio) as i32
+            }
+        });
         let center = look_from;
 
         // camera
@@ -83,52 +104,255 @@ impl Camera {
             defocus_angle,
             defocus_disk_u,
             defocus_disk_v,
+            shutter_open,
+            shutter_close,
+            spectral,
         }
     }
-    pub fn render(&self, world: &HittableList) {
-        print!("P3\n{} {}\n255\n", self.image_width, self.image_height);
-        let mut image: Vec<Color> =
-            Vec::with_capacity(self.image_height as usize * self.image_width as usize * 3);
-
-        for j in 0..self.image_height {
-            if (self.image_height - j) % 50 == 0 {
-                eprintln!("remaining scanlines: {}", self.image_height - j);
-            }
-            for i in 0..self.image_width {
-                let mut pixel_color = Color::new(0., 0., 0.);
-                for _ in 0..self.samples_per_pixel {
-                    let ray = self.ray_at(i, j);
-                    pixel_color += self.ray_color(&ray, world, self.max_depth);
-                }
 
-                image.push(pixel_color);
+    // scanlines are handed out from a shared queue, rows land in place so the
+    // output order does not depend on the number of threads
+    pub fn render(
+        &self,
+        world: &World<impl Hittable + Sync>,
+        threads: usize,
+        seed: Option<u64>,
+        progress: &(dyn Fn(usize, usize) + Sync),
+    ) -> Image {
+        let width = self.image_width as usize;
+        let height = self.image_height as usize;
+        let mut image = vec![Color::BLACK; width * height];
+
+        let scanlines = Mutex::new(image.chunks_mut(width).enumerate());
+        let finished = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..threads.max(1) {
+                s.spawn(|| loop {
+                    let next = scanlines.lock().unwrap().next();
+                    let Some((j, row)) = next else {
+                        break;
+                    };
+                    // reseeding per scanline keeps seeded renders reproducible
+                    // no matter which thread picks the row up
+                    if let Some(seed) = seed {
+                        fastrand::seed(seed ^ (j as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+                    }
+                    self.render_scanline(world, j as i32, row);
+
+                    let done = finished.fetch_add(1, Ordering::Relaxed) + 1;
+                    progress(done, height);
+                });
             }
+        });
+
+        Image {
+            width,
+            height,
+            pixels: image
+                .into_iter()
+                .map(|pixel| self.pixel_samples_scale * pixel)
+                .collect(),
         }
+    }
 
-        for pixel in image {
-            print!("{}", (self.pixel_samples_scale * pixel).format());
+    fn render_scanline(&self, world: &World<impl Hittable>, j: i32, row: &mut [Color]) {
+        for (i, pixel) in row.iter_mut().enumerate() {
+            let mut pixel_color = Color::new(0., 0., 0.);
+            for sample in 0..self.samples_per_pixel {
+                let mut ray = self.ray_at(i as i32, j);
+                let mut weight = Color::WHITE;
+                if self.spectral {
+                    // wavelengths are stratified over the samples of a pixel
+                    let u = (sample as f64 + random_f64()) / self.samples_per_pixel as f64;
+                    let (wavelength, spectral_weight) = spectrum::sample_wavelength(u);
+                    ray.wavelength = Some(wavelength);
+                    weight = spectral_weight;
+                }
+                pixel_color += weight * self.ray_color(&ray, world, None, None, self.max_depth);
+            }
+            *pixel = pixel_color;
         }
-        eprintln!("done");
     }
 
-    fn ray_color(&self, ray: &Ray, world: &impl Hittable, depth: u32) -> Color {
+    // `medium` is the participating medium the ray currently travels through.
+    // `last` is the point the ray was scattered from and the density it was
+    // sampled with, if light was also sampled there; emitters found by the
+    // ray are then weighed against the chance of sampling them directly.
+    fn ray_color<'a>(
+        &self,
+        ray: &Ray,
+        world: &World<'a, impl Hittable>,
+        medium: Option<&'a Medium>,
+        last: Option<(Point, f64)>,
+        depth: u32,
+    ) -> Color {
         if depth == 0 {
             return Color::BLACK;
         }
 
-        if let Some(hit_rec) = world.hit(ray, Interval::new(0.001, f64::INFINITY)) {
-            return if let Some((scattered_ray, attenuation)) =
-                hit_rec.material.scatter(ray, &hit_rec)
-            {
-                attenuation * self.ray_color(&scattered_ray, world, depth - 1)
-            } else {
-                Color::BLACK
+        let Some(hit_rec) = world.objects.hit(ray, Interval::new(0.001, f64::INFINITY)) else {
+            return world.environment.radiance(ray.dir);
+        };
+
+        let mut transmittance = 1.;
+        if let Some(medium) = medium {
+            match medium.interact(ray, hit_rec.t) {
+                Interaction::Scattered(point) => {
+                    let direct = self.direct_light(point, ray.time, world, |dir| {
+                        let phase = medium.phase(ray.dir, dir);
+                        (phase * medium.albedo, phase, Some(medium))
+                    });
+                    let dir = medium.sample_phase(ray.dir);
+                    let mut scattered_ray = Ray::new(point, dir, ray.time);
+                    scattered_ray.wavelength = ray.wavelength;
+                    let last = Some((point, medium.phase(ray.dir, dir)));
+                    return direct
+                        + medium.albedo
+                            * self.ray_color(&scattered_ray, world, Some(medium), last, depth - 1);
+                }
+                Interaction::Transmitted(t) => transmittance = t,
+            }
+        }
+
+        // a ray reaching a surface from behind travelled through the object
+        let mut absorbed = Color::WHITE;
+        if !hit_rec.front_face {
+            absorbed = hit_rec.material.transmittance(hit_rec.t * ray.dir.length());
+        }
+
+        let mut emitted = hit_rec.material.emitted(ray, &hit_rec);
+        if let Some((origin, pdf)) = last {
+            if !emitted.near_zero() {
+                let light_pdf = world
+                    .emitters
+                    .pdf_value(&Ray::new(origin, ray.dir, ray.time));
+                emitted = power_heuristic(pdf, light_pdf) * emitted;
+            }
+        }
+
+        let scattered = match hit_rec.material.scatter(ray, &hit_rec) {
+            None => Color::BLACK,
+            Some(Scatter::Specular(mut scattered_ray, attenuation)) => {
+                scattered_ray.wavelength = ray.wavelength;
+                // the boundary of a volume is no vertex of the path, emitters
+                // behind it are still weighed against the last real one
+                let last = match hit_rec.material {
+                    MaterialKind::Interface => last,
+                    _ => None,
+                };
+                let medium = next_medium(&hit_rec, scattered_ray.dir, medium);
+                attenuation * self.ray_color(&scattered_ray, world, medium, last, depth - 1)
+            }
+            Some(Scatter::Sampled {
+                ray: mut scattered_ray,
+                weight,
+                pdf,
+            }) => {
+                scattered_ray.wavelength = ray.wavelength;
+                let direct = self.direct_light(hit_rec.point, ray.time, world, |dir| {
+                    (
+                        hit_rec.material.eval(ray, &hit_rec, dir),
+                        hit_rec.material.pdf(ray, &hit_rec, dir),
+                        next_medium(&hit_rec, dir, medium),
+                    )
+                });
+                let indirect = if weight.near_zero() {
+                    Color::BLACK
+                } else {
+                    let medium = next_medium(&hit_rec, scattered_ray.dir, medium);
+                    let last = Some((hit_rec.point, pdf));
+                    weight * self.ray_color(&scattered_ray, world, medium, last, depth - 1)
+                };
+                direct + indirect
+            }
+        };
+
+        transmittance * absorbed * (emitted + scattered)
+    }
+
+    // next event estimation: every analytic light and a direction toward one
+    // of the emitters. `bsdf` gives the bsdf times cosine, the scattering
+    // density and the medium a ray leaving in a direction starts out in.
+    fn direct_light<'a>(
+        &self,
+        origin: Point,
+        time: f64,
+        world: &World<'a, impl Hittable>,
+        bsdf: impl Fn(Vec3) -> (Color, f64, Option<&'a Medium>),
+    ) -> Color {
+        let mut direct = Color::BLACK;
+
+        // scattered rays never find analytic lights, so there is nothing to
+        // weigh them against
+        for light in world.lights {
+            let Some(sample) = light.sample(origin) else {
+                continue;
             };
+            let (f, _, medium) = bsdf(sample.dir);
+            if f.near_zero() {
+                continue;
+            }
+            let shadow_ray = Ray::new(origin, sample.dir, time);
+            let (transmittance, blocker) = self.shadow(&shadow_ray, sample.distance, world, medium);
+            if blocker.is_none() {
+                direct += transmittance * f * sample.intensity;
+            }
         }
 
-        let unit_direction = ray.dir.normalized();
-        let a = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - a) * Color::new(1., 1., 1.) + a * Color::new(0.5, 0.7, 1.0)
+        if world.emitters.objects.is_empty() {
+            return direct;
+        }
+
+        let dir = world.emitters.random(origin, time);
+        let shadow_ray = Ray::new(origin, dir, time);
+        let light_pdf = world.emitters.pdf_value(&shadow_ray);
+        if light_pdf <= 0. {
+            return direct;
+        }
+
+        let (f, pdf, medium) = bsdf(dir);
+        if f.near_zero() {
+            return direct;
+        }
+        if let (transmittance, Some((ray, hit_rec))) =
+            self.shadow(&shadow_ray, f64::INFINITY, world, medium)
+        {
+            let weight = power_heuristic(light_pdf, pdf) / light_pdf;
+            direct += weight * transmittance * f * hit_rec.material.emitted(&ray, &hit_rec);
+        }
+        direct
+    }
+
+    // follows `ray` through the boundaries of volumes up to the first surface
+    // before `t_max`, returns the transmittance of the media on the way and
+    // the surface along with the last segment of the ray
+    fn shadow<'a>(
+        &self,
+        ray: &Ray,
+        mut t_max: f64,
+        world: &World<'a, impl Hittable>,
+        mut medium: Option<&'a Medium>,
+    ) -> (f64, Option<(Ray, HitRecord<'a>)>) {
+        let mut ray = *ray;
+        let mut transmittance = 1.;
+        loop {
+            let hit = world.objects.hit(&ray, Interval::new(0.001, t_max));
+            let t = hit.map_or(t_max, |hit_rec| hit_rec.t);
+            if let Some(medium) = medium {
+                if t.is_finite() {
+                    transmittance *= medium.transmittance(&ray, t);
+                }
+            }
+
+            match hit {
+                Some(hit_rec) if matches!(hit_rec.material, MaterialKind::Interface) => {
+                    medium = next_medium(&hit_rec, ray.dir, medium);
+                    ray = Ray::new(hit_rec.point, ray.dir, ray.time);
+                    t_max -= hit_rec.t;
+                }
+                hit => return (transmittance, hit.map(|hit_rec| (ray, hit_rec))),
+            }
+        }
     }
 
     fn ray_at(&self, i: i32, j: i32) -> Ray {
@@ -143,8 +367,9 @@ impl Camera {
             self.defocus_disk_sample()
         };
         let direction = pixel_sample - origin;
+        let time = random_f64_between(self.shutter_open, self.shutter_close);
 
-        Ray::new(origin, direction)
+        Ray::new(origin, direction, time)
     }
 
     fn defocus_disk_sample(&self) -> Point {
@@ -156,3 +381,76 @@ impl Camera {
         Vec3::new(random_f64() - 0.5, random_f64() - 0.5, 0.)
     }
 }
+
+// everything that is rendered, `emitters` are the emissive objects that are
+// sampled as lights
+pub struct World<'a, H> {
+    pub objects: &'a H,
+    pub emitters: &'a HittableList,
+    pub lights: &'a [LightKind],
+    pub environment: &'a Environment,
+}
+
+// crossing the boundary of a volume enters or leaves its medium, any other
+// surface leaves the current one unchanged
+fn next_medium<'a>(
+    hit_rec: &HitRecord<'a>,
+    dir: Vec3,
+    current: Option<&'a Medium>,
+) -> Option<&'a Medium> {
+    match hit_rec.medium {
+        Some(inner) => {
+            let outward = if hit_rec.front_face {
+                hit_rec.geometric_normal
+            } else {
+                -hit_rec.geometric_normal
+            };
+            (dir.dot(outward) < 0.).then_some(inner)
+        }
+        None => current,
+    }
+}
+
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0. {
+        return 0.;
+    }
+    a / (a + b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(image_width: i32, image_height: Option<i32>) -> Camera {
+        Camera::init(
+            16. / 9.,
+            image_width,
+            image_height,
+            1,
+            1,
+            90.,
+            Point::new(0., 0., 0.),
+            Point::new(0., 0., -1.),
+            Vec3::new(0., 1., 0.),
+            0.,
+            1.,
+            0.,
+            1.,
+            false,
+        )
+    }
+
+    #[test]
+    fn height_override_gives_the_requested_size() {
+        let camera = camera(400, Some(127));
+        assert_eq!((camera.image_width, camera.image_height), (400, 127));
+    }
+
+    #[test]
+    fn height_follows_the_aspect_ratio_when_omitted() {
+        let camera = camera(400, None);
+        assert_eq!((camera.image_width, camera.image_height), (400, 225));
+    }
+}
@@ -0,0 +1,69 @@
+use std::{f64::consts::PI, path::Path};
+
+use crate::{
+    texture::{Filter, ImageTexture, Texture, Wrap},
+    vec3::{Color, Vec3},
+};
+
+// radiance arriving from infinitely far away, seen by rays that escape the scene
+pub enum Environment {
+    Solid(Color),
+    Gradient { bottom: Color, top: Color },
+    Image(EnvironmentMap),
+}
+
+impl Environment {
+    pub fn radiance(&self, dir: Vec3) -> Color {
+        match self {
+            Environment::Solid(color) => *color,
+            Environment::Gradient { bottom, top } => {
+                let unit_direction = dir.normalized();
+                let a = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - a) * *bottom + a * *top
+            }
+            Environment::Image(map) => map.radiance(dir),
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::Gradient {
+            bottom: Color::new(1., 1., 1.),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+// equirectangular image, the top row looks straight up and the horizontal
+// center looks down -z before rotation
+pub struct EnvironmentMap {
+    texture: ImageTexture,
+    rotation: f64,
+    intensity: f64,
+}
+
+impl EnvironmentMap {
+    pub fn load(path: &Path, rotation: f64, intensity: f64) -> image::ImageResult<Self> {
+        let mut texture = ImageTexture::load(path, None)?;
+        texture.filter = Filter::Bilinear;
+        texture.wrap_u = Wrap::Repeat;
+        texture.wrap_v = Wrap::Clamp;
+
+        Ok(EnvironmentMap {
+            texture,
+            rotation: rotation.to_radians(),
+            intensity,
+        })
+    }
+
+    fn radiance(&self, dir: Vec3) -> Color {
+        let d = dir.normalized();
+        let phi = d.x().atan2(-d.z()) - self.rotation;
+        let theta = d.y().clamp(-1., 1.).acos();
+
+        let u = phi / (2. * PI) + 0.5;
+        let v = 1. - theta / PI;
+        self.intensity * self.texture.value(u, v, &d)
+    }
+}
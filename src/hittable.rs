@@ -1,27 +1,46 @@
 use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    instance::Instance,
     interval::Interval,
     material::MaterialKind,
+    medium::{ConstantMedium, Medium, Volume},
+    onb::Onb,
+    quad::{Cuboid, Disk, Plane, Quad},
+    random::random_index,
     ray::Ray,
     sphere::Sphere,
+    triangle::{Triangle, TriangleMesh},
     vec3::{Point, Vec3},
 };
 
 #[derive(Clone, Copy, Debug)]
-pub struct HitRecord {
+pub struct HitRecord<'a> {
     pub point: Point,
+    // the shading normal and the normal of the surface itself, both on the
+    // side the ray came from
     pub normal: Vec3,
+    pub geometric_normal: Vec3,
+    // derivatives of the point over the texture coordinates u and v
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
-    pub material: MaterialKind,
+    pub material: &'a MaterialKind,
+    // the medium enclosed by the surface, if any
+    pub medium: Option<&'a Medium>,
 }
 
-impl HitRecord {
+impl<'a> HitRecord<'a> {
     pub fn new(
         ray: &Ray,
         point: &Point,
         t: f64,
         outward_normal: Vec3,
-        material: MaterialKind,
+        (u, v): (f64, f64),
+        material: &'a MaterialKind,
     ) -> Self {
         let front_face = ray.dir.dot(outward_normal) < 0.;
         let normal = if front_face {
@@ -29,29 +48,153 @@ impl HitRecord {
         } else {
             -outward_normal.to_owned()
         };
+        let frame = Onb::from_normal(outward_normal);
 
         HitRecord {
             point: point.to_owned(),
             normal,
+            geometric_normal: normal,
+            tangent: frame.u,
+            bitangent: frame.v,
             t,
+            u,
+            v,
             front_face,
             material,
+            medium: None,
+        }
+    }
+
+    pub fn with_tangents(self, tangent: Vec3, bitangent: Vec3) -> Self {
+        HitRecord {
+            tangent,
+            bitangent,
+            ..self
+        }
+    }
+
+    // the side of the surface stays the one of the geometric normal, so that
+    // interpolated normals do not let light through
+    pub fn with_shading_normal(self, outward_normal: Vec3) -> Self {
+        HitRecord {
+            normal: if self.front_face {
+                outward_normal
+            } else {
+                -outward_normal
+            },
+            ..self
+        }
+    }
+
+    // the shading normal as it faces out of the surface
+    pub fn outward_normal(&self) -> Vec3 {
+        if self.front_face {
+            self.normal
+        } else {
+            -self.normal
+        }
+    }
+
+    // orthonormal frame around the shading normal with u along the tangent
+    pub fn frame(&self) -> Onb {
+        self.frame_around(self.normal)
+    }
+
+    // falls back to an arbitrary tangent where the texture coordinates are
+    // degenerate
+    pub fn frame_around(&self, normal: Vec3) -> Onb {
+        let u = self.tangent - self.tangent.dot(normal) * normal;
+        let length = u.length();
+        if length <= 1e-9 * self.tangent.length() || !length.is_finite() {
+            return Onb::from_normal(normal);
+        }
+        let u = u / length;
+        Onb {
+            u,
+            v: normal.cross(u),
+            w: normal,
         }
     }
 }
 
 pub trait Hittable {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>>;
+
+    fn bounding_box(&self) -> Aabb;
+
+    // density over directions of `random` toward this object, evaluated for
+    // the direction of `ray` as seen from its origin
+    fn pdf_value(&self, _ray: &Ray) -> f64 {
+        0.
+    }
+
+    // a direction from `origin` toward a random point of the object
+    fn random(&self, _origin: Point, _time: f64) -> Vec3 {
+        Vec3::new(1., 0., 0.)
+    }
 }
 
 pub enum HittableKind {
     Sphere(Sphere),
+    Bvh(BvhNode),
+    Triangle(Triangle),
+    TriangleMesh(TriangleMesh),
+    Quad(Quad),
+    Plane(Plane),
+    Disk(Disk),
+    Box(Cuboid),
+    Instance(Instance),
+    ConstantMedium(ConstantMedium),
+    Volume(Volume),
 }
 
 impl Hittable for HittableKind {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
         match self {
             HittableKind::Sphere(sphere) => sphere.hit(ray, ray_t),
+            HittableKind::Bvh(bvh) => bvh.hit(ray, ray_t),
+            HittableKind::Triangle(triangle) => triangle.hit(ray, ray_t),
+            HittableKind::TriangleMesh(mesh) => mesh.hit(ray, ray_t),
+            HittableKind::Quad(quad) => quad.hit(ray, ray_t),
+            HittableKind::Plane(plane) => plane.hit(ray, ray_t),
+            HittableKind::Disk(disk) => disk.hit(ray, ray_t),
+            HittableKind::Box(cuboid) => cuboid.hit(ray, ray_t),
+            HittableKind::Instance(instance) => instance.hit(ray, ray_t),
+            HittableKind::ConstantMedium(medium) => medium.hit(ray, ray_t),
+            HittableKind::Volume(volume) => volume.hit(ray, ray_t),
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            HittableKind::Sphere(sphere) => sphere.bounding_box(),
+            HittableKind::Bvh(bvh) => bvh.bounding_box(),
+            HittableKind::Triangle(triangle) => triangle.bounding_box(),
+            HittableKind::TriangleMesh(mesh) => mesh.bounding_box(),
+            HittableKind::Quad(quad) => quad.bounding_box(),
+            HittableKind::Plane(plane) => plane.bounding_box(),
+            HittableKind::Disk(disk) => disk.bounding_box(),
+            HittableKind::Box(cuboid) => cuboid.bounding_box(),
+            HittableKind::Instance(instance) => instance.bounding_box(),
+            HittableKind::ConstantMedium(medium) => medium.bounding_box(),
+            HittableKind::Volume(volume) => volume.bounding_box(),
+        }
+    }
+
+    // only spheres and quads can be sampled as lights
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        match self {
+            HittableKind::Sphere(sphere) => sphere.pdf_value(ray),
+            HittableKind::Quad(quad) => quad.pdf_value(ray),
+            _ => 0.,
+        }
+    }
+
+    fn random(&self, origin: Point, time: f64) -> Vec3 {
+        match self {
+            HittableKind::Sphere(sphere) => sphere.random(origin, time),
+            HittableKind::Quad(quad) => quad.random(origin, time),
+            _ => Vec3::new(1., 0., 0.),
         }
     }
 }
@@ -60,8 +203,29 @@ pub struct HittableList {
     pub objects: Vec<HittableKind>,
 }
 
+impl HittableList {
+    // copies of the emissive spheres and quads at the top level, lights
+    // nested in groups or instances are only found by scattered rays
+    pub fn emitters(&self) -> HittableList {
+        let objects = self
+            .objects
+            .iter()
+            .filter_map(|obj| match obj {
+                HittableKind::Sphere(sphere) if sphere.material.is_emissive() => {
+                    Some(HittableKind::Sphere(sphere.clone()))
+                }
+                HittableKind::Quad(quad) if quad.material.is_emissive() => {
+                    Some(HittableKind::Quad(quad.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        HittableList { objects }
+    }
+}
+
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
         let mut current_hit = None;
         let mut interval = ray_t;
 
@@ -74,4 +238,23 @@ impl Hittable for HittableList {
 
         current_hit
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::EMPTY, |bbox, obj| {
+            Aabb::surrounding(bbox, obj.bounding_box())
+        })
+    }
+
+    // every object is picked with the same probability
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        if self.objects.is_empty() {
+            return 0.;
+        }
+        let sum: f64 = self.objects.iter().map(|obj| obj.pdf_value(ray)).sum();
+        sum / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: Point, time: f64) -> Vec3 {
+        self.objects[random_index(self.objects.len())].random(origin, time)
+    }
 }
@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, HittableKind},
+    interval::Interval,
+    ray::Ray,
+    transform::AnimatedTransform,
+};
+
+// places a shared object in the world, rays are moved into the object's own
+// space instead of moving the object; the direction is not renormalized so
+// hit distances are the same in both spaces
+pub struct Instance {
+    object: Arc<HittableKind>,
+    transform: AnimatedTransform,
+    bbox: Aabb,
+}
+
+impl Instance {
+    pub fn new(object: Arc<HittableKind>, transform: AnimatedTransform) -> Instance {
+        let bbox = transform.bounding_box(object.bounding_box());
+        Instance {
+            object,
+            transform,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let to_world = self.transform.at(ray.time);
+        let to_object = to_world.inverse();
+        let local = Ray::new(
+            to_object.point(ray.origin),
+            to_object.vector(ray.dir),
+            ray.time,
+        );
+
+        let mut hit_rec = self.object.hit(&local, ray_t)?;
+        hit_rec.point = to_world.point(hit_rec.point);
+        hit_rec.normal = to_world.normal(hit_rec.normal).normalized();
+        hit_rec.geometric_normal = to_world.normal(hit_rec.geometric_normal).normalized();
+        hit_rec.tangent = to_world.vector(hit_rec.tangent);
+        hit_rec.bitangent = to_world.vector(hit_rec.bitangent);
+        Some(hit_rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
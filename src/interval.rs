@@ -7,29 +7,30 @@ pub struct Interval {
 }
 
 impl Interval {
-    pub fn default() -> Interval {
-        return Interval {
-            max: f64::INFINITY,
-            min: f64::NEG_INFINITY,
-        };
+    pub fn new(min: f64, max: f64) -> Interval {
+        Interval { max, min }
     }
 
-    pub fn new(min: f64, max: f64) -> Interval {
-        return Interval { max, min };
+    pub fn surrounding(a: Interval, b: Interval) -> Interval {
+        Interval {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
     }
 
     pub fn size(&self) -> f64 {
         self.max - self.min
     }
 
-    pub fn contains(&self, x: f64) -> bool {
-        self.min <= x && x <= self.max
-    }
-
     pub fn surrounds(&self, x: f64) -> bool {
         self.min < x && x < self.max
     }
 
+    pub fn expand(&self, delta: f64) -> Interval {
+        let padding = delta / 2.;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
     pub fn clamp(&self, x: f64) -> f64 {
         if x <= self.min {
             self.min
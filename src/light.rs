@@ -0,0 +1,161 @@
+use crate::{
+    onb::Onb,
+    random::random_f64,
+    vec3::{Color, Point, Vec3},
+};
+
+// light arriving at a point from `dir` (a unit vector toward the light), from
+// `distance` away along it
+pub struct LightSample {
+    pub dir: Vec3,
+    pub distance: f64,
+    pub intensity: Color,
+}
+
+// lights without a surface, rays never hit them and they only show through
+// the light they shed
+pub trait Light {
+    fn sample(&self, point: Point) -> Option<LightSample>;
+}
+
+#[derive(Clone, Debug)]
+pub enum LightKind {
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+}
+
+impl Light for LightKind {
+    fn sample(&self, point: Point) -> Option<LightSample> {
+        match self {
+            LightKind::Point(light) => light.sample(point),
+            LightKind::Spot(light) => light.sample(point),
+            LightKind::Directional(light) => light.sample(point),
+        }
+    }
+}
+
+// `intensity` falls off with the square of the distance
+#[derive(Clone, Debug)]
+pub struct PointLight {
+    position: Point,
+    intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point, intensity: Color) -> PointLight {
+        PointLight {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: Point) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance_squared = to_light.length_squared();
+        if distance_squared == 0. {
+            return None;
+        }
+
+        Some(LightSample {
+            dir: to_light / distance_squared.sqrt(),
+            distance: distance_squared.sqrt(),
+            intensity: self.intensity / distance_squared,
+        })
+    }
+}
+
+// a point light shining along `direction`, at full strength up to
+// `inner_angle` off its axis and fading out smoothly toward `outer_angle`
+#[derive(Clone, Debug)]
+pub struct SpotLight {
+    light: PointLight,
+    direction: Vec3,
+    cos_inner: f64,
+    cos_outer: f64,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point,
+        direction: Vec3,
+        intensity: Color,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> SpotLight {
+        SpotLight {
+            light: PointLight::new(position, intensity),
+            direction: direction.normalized(),
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_inner {
+            return 1.;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.;
+        }
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3. - 2. * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: Point) -> Option<LightSample> {
+        let sample = self.light.sample(point)?;
+        let falloff = self.falloff(-sample.dir.dot(self.direction));
+        if falloff == 0. {
+            return None;
+        }
+
+        Some(LightSample {
+            intensity: falloff * sample.intensity,
+            ..sample
+        })
+    }
+}
+
+// a light infinitely far away such as the sun, `intensity` is what arrives on
+// a surface facing it. A disk of `angular_diameter` instead of a single
+// direction casts soft shadows.
+#[derive(Clone, Debug)]
+pub struct DirectionalLight {
+    frame: Onb,
+    intensity: Color,
+    cos_max: f64,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, intensity: Color, angular_diameter: f64) -> DirectionalLight {
+        DirectionalLight {
+            frame: Onb::new(-direction),
+            intensity,
+            cos_max: (0.5 * angular_diameter).to_radians().cos(),
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _: Point) -> Option<LightSample> {
+        let dir = if self.cos_max >= 1. {
+            self.frame.w
+        } else {
+            let z = 1. + random_f64() * (self.cos_max - 1.);
+            let phi = 2. * std::f64::consts::PI * random_f64();
+            let r = (1. - z * z).sqrt();
+            self.frame
+                .transform(Vec3::new(phi.cos() * r, phi.sin() * r, z))
+        };
+
+        Some(LightSample {
+            dir,
+            distance: f64::INFINITY,
+            intensity: self.intensity,
+        })
+    }
+}
@@ -1,36 +1,61 @@
+mod aabb;
+mod bvh;
 mod camera;
+mod environment;
 mod hittable;
+mod instance;
 mod interval;
+mod light;
 mod material;
+mod medium;
+mod microfacet;
+mod noise;
+mod obj;
+mod onb;
+mod output;
+mod quad;
 mod random;
 mod ray;
+mod scene;
+mod spectrum;
 mod sphere;
+mod texture;
+mod transform;
+mod triangle;
 mod vec3;
 
 use core::f64;
+use std::{
+    fmt,
+    fs::File,
+    path::{Path, PathBuf},
+    process, thread,
+};
 
-use camera::Camera;
+use clap::Parser;
+
+use bvh::{BvhNode, SplitStrategy};
+use camera::World;
+use environment::Environment;
 use hittable::{HittableKind, HittableList};
 use material::{Dielectric, Lambertian, MaterialKind, Metal};
+use output::ImageFormat;
 use random::{random_f64, random_f64_between};
+use scene::{CameraSettings, Scene};
 use sphere::Sphere;
 use vec3::{Color, Point, Vec3};
 
 fn old_world() -> HittableList {
     let mat_ground = MaterialKind::Lambertian(Lambertian {
-        albedo: Color::new(0.8, 0.8, 0.),
+        albedo: Color::new(0.8, 0.8, 0.).into(),
     });
     let mat_center = MaterialKind::Lambertian(Lambertian {
-        albedo: Color::new(0.1, 0.2, 0.5),
-    });
-    let mat_left = MaterialKind::Dielectric(Dielectric {
-        refraction_index: 1.5,
-    });
-    let mat_bubble = MaterialKind::Dielectric(Dielectric {
-        refraction_index: 1. / 1.5,
+        albedo: Color::new(0.1, 0.2, 0.5).into(),
     });
+    let mat_left = MaterialKind::Dielectric(Dielectric::new(1.5));
+    let mat_bubble = MaterialKind::Dielectric(Dielectric::new(1. / 1.5));
     let mat_right = MaterialKind::Metal(Metal {
-        albedo: Color::new(0.8, 0.6, 0.2),
+        albedo: Color::new(0.8, 0.6, 0.2).into(),
         fuzz: 1.,
     });
 
@@ -68,10 +93,10 @@ fn old_world() -> HittableList {
 fn new_world() -> HittableList {
     let mut world = HittableList { objects: vec![] };
     let mat_left = MaterialKind::Lambertian(Lambertian {
-        albedo: Color::new(0., 0., 1.),
+        albedo: Color::new(0., 0., 1.).into(),
     });
     let mat_right = MaterialKind::Lambertian(Lambertian {
-        albedo: Color::new(1., 0., 0.),
+        albedo: Color::new(1., 0., 0.).into(),
     });
 
     let r = (f64::consts::FRAC_PI_4).cos();
@@ -93,7 +118,7 @@ fn final_world() -> HittableList {
     let mut world = HittableList { objects: vec![] };
 
     let ground_material = MaterialKind::Lambertian(Lambertian {
-        albedo: Color::new(0.5, 0.5, 0.5),
+        albedo: Color::new(0.5, 0.5, 0.5).into(),
     });
     world.objects.push(HittableKind::Sphere(Sphere::new(
         Point::new(0., -1000., 0.),
@@ -114,15 +139,18 @@ fn final_world() -> HittableList {
             if (center - Point::new(4., 0.2, 0.)).length() > 0.9 {
                 let sphere_mat = if choose_mat < 0.8 {
                     let albedo = Color::random() * Color::random();
-                    MaterialKind::Lambertian(Lambertian { albedo })
+                    MaterialKind::Lambertian(Lambertian {
+                        albedo: albedo.into(),
+                    })
                 } else if choose_mat < 0.95 {
                     let albedo = Color::random();
                     let fuzz = random_f64_between(0., 0.5);
-                    MaterialKind::Metal(Metal { albedo, fuzz })
-                } else {
-                    MaterialKind::Dielectric(Dielectric {
-                        refraction_index: 1.5,
+                    MaterialKind::Metal(Metal {
+                        albedo: albedo.into(),
+                        fuzz,
                     })
+                } else {
+                    MaterialKind::Dielectric(Dielectric::new(1.5))
                 };
                 world
                     .objects
@@ -132,16 +160,14 @@ fn final_world() -> HittableList {
     }
     eprintln!("sphere creation done");
 
-    let mat1 = MaterialKind::Dielectric(Dielectric {
-        refraction_index: 1.5,
-    });
+    let mat1 = MaterialKind::Dielectric(Dielectric::new(1.5));
     world.objects.push(HittableKind::Sphere(Sphere::new(
         Point::new(0., 1., 0.),
         1.,
         mat1,
     )));
     let mat2 = MaterialKind::Lambertian(Lambertian {
-        albedo: Color::new(0.4, 0.2, 0.1),
+        albedo: Color::new(0.4, 0.2, 0.1).into(),
     });
     world.objects.push(HittableKind::Sphere(Sphere::new(
         Point::new(-4., 1., 0.),
@@ -149,7 +175,7 @@ fn final_world() -> HittableList {
         mat2,
     )));
     let mat3 = MaterialKind::Metal(Metal {
-        albedo: Color::new(0.7, 0.6, 0.5),
+        albedo: Color::new(0.7, 0.6, 0.5).into(),
         fuzz: 0.,
     });
     world.objects.push(HittableKind::Sphere(Sphere::new(
@@ -161,33 +187,219 @@ fn final_world() -> HittableList {
     world
 }
 
+/// Renders one of the built-in worlds or a TOML scene file.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Built-in world (old_world, new_world, final_world) or path to a scene file
+    #[arg(default_value = "final_world")]
+    scene: String,
+
+    /// Image width in pixels
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    width: Option<i32>,
+
+    /// Image height in pixels, derived from the aspect ratio when omitted
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    height: Option<i32>,
+
+    /// Samples per pixel
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    spp: Option<u32>,
+
+    /// Maximum number of bounces per path
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    depth: Option<u32>,
+
+    /// Vertical field of view in degrees
+    #[arg(long)]
+    vfov: Option<f64>,
+
+    /// Camera position as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    look_from: Option<[f64; 3]>,
+
+    /// Point the camera looks at as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    look_at: Option<[f64; 3]>,
+
+    /// Camera up direction as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    vup: Option<[f64; 3]>,
+
+    /// Aperture cone angle in degrees, 0 disables depth of field
+    #[arg(long, allow_hyphen_values = true)]
+    defocus_angle: Option<f64>,
+
+    /// Distance to the plane of perfect focus
+    #[arg(long, allow_hyphen_values = true)]
+    focus_dist: Option<f64>,
+
+    /// Trace a wavelength per sample so that dielectrics with dispersion split light into colors
+    #[arg(long)]
+    spectral: bool,
+
+    /// Output file
+    #[arg(short, long, default_value = "output.png")]
+    output: PathBuf,
+
+    /// Output format, inferred from the output file extension when omitted
+    #[arg(long, value_enum)]
+    format: Option<ImageFormat>,
+
+    /// Print machine-readable progress lines (`progress <done> <total>`) on stdout
+    #[arg(long)]
+    progress: bool,
+
+    /// Number of worker threads, defaults to the available parallelism
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    threads: Option<u64>,
+
+    /// Seed for scene generation and sampling, renders are reproducible when set
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// BVH split strategy
+    #[arg(long, value_enum, default_value_t = SplitStrategy::Sah)]
+    bvh: SplitStrategy,
+}
+
+fn parse_vec3(s: &str) -> Result<[f64; 3], String> {
+    let parts: Vec<&str> = s.split(',').collect();
+    let [x, y, z] = parts[..] else {
+        return Err(format!("expected x,y,z, got `{}`", s));
+    };
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<f64>()
+            .map_err(|_| format!("invalid number `{}`", v))
+    };
+    Ok([parse(x)?, parse(y)?, parse(z)?])
+}
+
+fn builtin_camera() -> CameraSettings {
+    CameraSettings {
+        aspect_ratio: 16.0 / 9.0,
+        image_width: 1200,
+        image_height: None,
+        samples_per_pixel: 500,
+        max_depth: 50,
+        vfov: 20.,
+        look_from: [13., 2., 3.],
+        look_at: [0., 0., 0.],
+        vup: [0., 1., 0.],
+        defocus_angle: 0.6,
+        focus_dist: 10.,
+        shutter_open: 0.,
+        shutter_close: 1.,
+        spectral: false,
+    }
+}
+
+fn fail(message: impl fmt::Display) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
 fn main() {
-    let aspect_ratio = 16.0 / 9.0;
-    let image_width = 1200;
-    let samples_per_pixel = 500;
-    let max_depth = 50;
-
-    let vfov: f64 = 20.;
-    let look_from = Vec3::new(13., 2., 3.);
-    let look_at = Vec3::new(0., 0., 0.);
-    let vup = Vec3::new(0., 1., 0.);
-    let defocus_angle = 0.6;
-    let focus_dist = 10.;
-
-    let camera = Camera::init(
-        aspect_ratio,
-        image_width,
-        samples_per_pixel,
-        max_depth,
-        vfov,
-        look_from,
-        look_at,
-        vup,
-        defocus_angle,
-        focus_dist,
+    let args = Args::parse();
+
+    if let Some(seed) = args.seed {
+        fastrand::seed(seed);
+    }
+
+    let builtin = |world| Scene {
+        camera: builtin_camera(),
+        world,
+        lights: vec![],
+        environment: Environment::default(),
+    };
+    let mut scene = match args.scene.as_str() {
+        "old_world" => builtin(old_world()),
+        "new_world" => builtin(new_world()),
+        "final_world" => builtin(final_world()),
+        path => Scene::load(Path::new(path)).unwrap_or_else(|err| fail(err)),
+    };
+    let camera = &mut scene.camera;
+
+    if let Some(width) = args.width {
+        camera.image_width = width;
+    }
+    if let Some(height) = args.height {
+        camera.image_height = Some(height);
+    }
+    if let Some(spp) = args.spp {
+        camera.samples_per_pixel = spp;
+    }
+    if let Some(depth) = args.depth {
+        camera.max_depth = depth;
+    }
+    if let Some(vfov) = args.vfov {
+        camera.vfov = vfov;
+    }
+    if let Some(look_from) = args.look_from {
+        camera.look_from = look_from;
+    }
+    if let Some(look_at) = args.look_at {
+        camera.look_at = look_at;
+    }
+    if let Some(vup) = args.vup {
+        camera.vup = vup;
+    }
+    if let Some(defocus_angle) = args.defocus_angle {
+        camera.defocus_angle = defocus_angle;
+    }
+    if let Some(focus_dist) = args.focus_dist {
+        camera.focus_dist = focus_dist;
+    }
+    if args.spectral {
+        camera.spectral = true;
+    }
+    if let Err((key, message)) = camera.validate() {
+        fail(format_args!("{}: {}", key, message));
+    }
+
+    let format = args
+        .format
+        .or_else(|| ImageFormat::from_path(&args.output))
+        .unwrap_or_else(|| {
+            fail(format_args!(
+                "cannot infer the image format of {}, pass --format",
+                args.output.display()
+            ))
+        });
+    // opened before rendering so a bad path fails right away
+    let output = File::create(&args.output)
+        .unwrap_or_else(|err| fail(format_args!("{}: {}", args.output.display(), err)));
+
+    let threads = args.threads.map_or_else(
+        || thread::available_parallelism().map_or(1, |n| n.get()),
+        |n| n as usize,
     );
 
-    let world = final_world();
+    let emitters = scene.world.emitters();
+    let world = HittableKind::Bvh(BvhNode::new(scene.world, args.bvh));
+    let world = World {
+        objects: &world,
+        emitters: &emitters,
+        lights: &scene.lights,
+        environment: &scene.environment,
+    };
 
-    camera.render(&world);
+    let progress = |done: usize, total: usize| {
+        if args.progress {
+            println!("progress {} {}", done, total);
+        } else if (total - done).is_multiple_of(50) {
+            eprintln!("remaining scanlines: {}", total - done);
+        }
+    };
+    let image = scene
+        .camera
+        .build()
+        .render(&world, threads, args.seed, &progress);
+    eprintln!("done");
+
+    if let Err(err) = format.write(output, &image) {
+        fail(format_args!("{}: {}", args.output.display(), err));
+    }
 }
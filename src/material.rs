@@ -1,79 +1,385 @@
+use std::f64::consts::PI;
+
+use serde::Deserialize;
+
 use crate::{
     hittable::HitRecord,
+    microfacet::{fresnel_conductor, Ggx},
+    onb::Onb,
     random::random_f64,
     ray::Ray,
+    texture::{Texture, TextureKind},
     vec3::{Color, Vec3},
 };
 
+pub enum Scatter {
+    // mirror reflection and refraction have no density to weigh light samples
+    // against, the ray is followed with a plain attenuation
+    Specular(Ray, Color),
+    // `weight` is the bsdf times the cosine over `pdf` for the sampled ray
+    Sampled { ray: Ray, weight: Color, pdf: f64 },
+}
+
+impl Scatter {
+    // a sampled ray that carries nothing, so that light sampling still
+    // happens at the surface
+    fn lost(ray_in: &Ray, hit_rec: &HitRecord) -> Scatter {
+        Scatter::Sampled {
+            ray: Ray::new(hit_rec.point, hit_rec.normal, ray_in.time),
+            weight: Color::BLACK,
+            pdf: 0.,
+        }
+    }
+}
+
 pub trait Material {
-    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<(Ray, Color)>;
+    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<Scatter>;
+
+    // bsdf times the cosine for light leaving toward `dir`
+    fn eval(&self, _ray_in: &Ray, _hit_rec: &HitRecord, _dir: Vec3) -> Color {
+        Color::BLACK
+    }
+
+    // density with which `scatter` picks `dir`
+    fn pdf(&self, _ray_in: &Ray, _hit_rec: &HitRecord, _dir: Vec3) -> f64 {
+        0.
+    }
+
+    fn emitted(&self, _ray_in: &Ray, _hit_rec: &HitRecord) -> Color {
+        Color::BLACK
+    }
+
+    // what is left of light that travelled `distance` through the inside of
+    // the object before reaching its surface
+    fn transmittance(&self, _distance: f64) -> Color {
+        Color::WHITE
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum MaterialKind {
     Metal(Metal),
+    Conductor(Conductor),
     Lambertian(Lambertian),
     Dielectric(Dielectric),
+    Principled(Principled),
+    DiffuseLight(DiffuseLight),
+    Isotropic(Isotropic),
+    Bumped(Box<Bumped>),
+    Interface,
+}
+
+impl MaterialKind {
+    pub fn is_emissive(&self) -> bool {
+        match self {
+            MaterialKind::DiffuseLight(_) => true,
+            MaterialKind::Bumped(b) => b.material.is_emissive(),
+            _ => false,
+        }
+    }
 }
 
 impl Material for MaterialKind {
-    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<Scatter> {
         match self {
             MaterialKind::Metal(m) => m.scatter(ray_in, hit_rec),
+            MaterialKind::Conductor(c) => c.scatter(ray_in, hit_rec),
             MaterialKind::Lambertian(l) => l.scatter(ray_in, hit_rec),
             MaterialKind::Dielectric(d) => d.scatter(ray_in, hit_rec),
+            MaterialKind::Principled(p) => p.scatter(ray_in, hit_rec),
+            MaterialKind::DiffuseLight(d) => d.scatter(ray_in, hit_rec),
+            MaterialKind::Isotropic(i) => i.scatter(ray_in, hit_rec),
+            MaterialKind::Bumped(b) => b.scatter(ray_in, hit_rec),
+            // the invisible boundary of a medium
+            MaterialKind::Interface => Some(Scatter::Specular(
+                Ray::new(hit_rec.point, ray_in.dir, ray_in.time),
+                Color::WHITE,
+            )),
+        }
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> Color {
+        match self {
+            MaterialKind::Metal(m) => m.eval(ray_in, hit_rec, dir),
+            MaterialKind::Conductor(c) => c.eval(ray_in, hit_rec, dir),
+            MaterialKind::Lambertian(l) => l.eval(ray_in, hit_rec, dir),
+            MaterialKind::Dielectric(d) => d.eval(ray_in, hit_rec, dir),
+            MaterialKind::Principled(p) => p.eval(ray_in, hit_rec, dir),
+            MaterialKind::DiffuseLight(d) => d.eval(ray_in, hit_rec, dir),
+            MaterialKind::Isotropic(i) => i.eval(ray_in, hit_rec, dir),
+            MaterialKind::Bumped(b) => b.eval(ray_in, hit_rec, dir),
+            MaterialKind::Interface => Color::BLACK,
+        }
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> f64 {
+        match self {
+            MaterialKind::Metal(m) => m.pdf(ray_in, hit_rec, dir),
+            MaterialKind::Conductor(c) => c.pdf(ray_in, hit_rec, dir),
+            MaterialKind::Lambertian(l) => l.pdf(ray_in, hit_rec, dir),
+            MaterialKind::Dielectric(d) => d.pdf(ray_in, hit_rec, dir),
+            MaterialKind::Principled(p) => p.pdf(ray_in, hit_rec, dir),
+            MaterialKind::DiffuseLight(d) => d.pdf(ray_in, hit_rec, dir),
+            MaterialKind::Isotropic(i) => i.pdf(ray_in, hit_rec, dir),
+            MaterialKind::Bumped(b) => b.pdf(ray_in, hit_rec, dir),
+            MaterialKind::Interface => 0.,
+        }
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Color {
+        match self {
+            MaterialKind::Metal(m) => m.emitted(ray_in, hit_rec),
+            MaterialKind::Conductor(c) => c.emitted(ray_in, hit_rec),
+            MaterialKind::Lambertian(l) => l.emitted(ray_in, hit_rec),
+            MaterialKind::Dielectric(d) => d.emitted(ray_in, hit_rec),
+            MaterialKind::Principled(p) => p.emitted(ray_in, hit_rec),
+            MaterialKind::DiffuseLight(d) => d.emitted(ray_in, hit_rec),
+            MaterialKind::Isotropic(i) => i.emitted(ray_in, hit_rec),
+            MaterialKind::Bumped(b) => b.emitted(ray_in, hit_rec),
+            MaterialKind::Interface => Color::BLACK,
+        }
+    }
+
+    fn transmittance(&self, distance: f64) -> Color {
+        match self {
+            MaterialKind::Dielectric(d) => d.transmittance(distance),
+            MaterialKind::Principled(p) => p.transmittance(distance),
+            MaterialKind::Bumped(b) => b.transmittance(distance),
+            _ => Color::WHITE,
         }
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Metal {
-    pub albedo: Color,
+    pub albedo: TextureKind,
     pub fuzz: f64,
 }
 
+// fuzzy reflections are spread evenly over a sphere of radius `fuzz` around
+// the tip of the mirror direction, rays ending up below the surface are
+// absorbed
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<(Ray, Color)> {
-        let mut reflected = ray_in.dir.reflect(hit_rec.normal);
-        reflected = reflected.normalized() + (self.fuzz * Vec3::random_unit_vector());
-        let scattered = Ray::new(hit_rec.point, reflected);
-        if scattered.dir.dot(hit_rec.normal) > 0. {
-            Some((scattered, self.albedo))
+    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<Scatter> {
+        let reflected = ray_in.dir.reflect(hit_rec.normal).normalized();
+        let attenuation = self.albedo.value(hit_rec.u, hit_rec.v, &hit_rec.point);
+        if self.fuzz == 0. {
+            let scattered = Ray::new(hit_rec.point, reflected, ray_in.time);
+            return Some(Scatter::Specular(scattered, attenuation));
+        }
+
+        let dir = reflected + self.fuzz * Vec3::random_unit_vector();
+        let weight = if dir.dot(hit_rec.normal) > 0. {
+            attenuation
         } else {
-            None
+            Color::BLACK
+        };
+        Some(Scatter::Sampled {
+            ray: Ray::new(hit_rec.point, dir, ray_in.time),
+            weight,
+            pdf: self.pdf(ray_in, hit_rec, dir),
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> Color {
+        if self.fuzz == 0. || dir.dot(hit_rec.normal) <= 0. {
+            return Color::BLACK;
         }
+        self.pdf(ray_in, hit_rec, dir) * self.albedo.value(hit_rec.u, hit_rec.v, &hit_rec.point)
+    }
+
+    // the ray along `dir` crosses the fuzz sphere where t^2 - 2bt + 1 - fuzz^2
+    // vanishes, each crossing contributes the uniform area density turned into
+    // solid angle by t^2 / |cos|, and |cos| works out to sqrt(disc) / fuzz
+    fn pdf(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> f64 {
+        if self.fuzz == 0. {
+            return 0.;
+        }
+        let reflected = ray_in.dir.reflect(hit_rec.normal).normalized();
+        let b = dir.normalized().dot(reflected);
+        let disc = b * b - 1. + self.fuzz * self.fuzz;
+        if disc <= 0. {
+            return 0.;
+        }
+
+        let sqrt_disc = disc.sqrt();
+        [b - sqrt_disc, b + sqrt_disc]
+            .into_iter()
+            .filter(|&t| t > 0.)
+            .map(|t| t * t / (4. * PI * self.fuzz * sqrt_disc))
+            .sum()
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+// rough metal with a GGX microfacet distribution, reflecting by the Fresnel
+// equations for the complex index of refraction eta + ik
+#[derive(Clone, Debug)]
+pub struct Conductor {
+    pub eta: Color,
+    pub k: Color,
+    // None for a perfect mirror
+    pub distribution: Option<Ggx>,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64, anisotropy: f64) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            distribution: (roughness > 0.).then(|| Ggx::new(roughness, anisotropy)),
+        }
+    }
+
+    // the outgoing direction in the surface frame, None from below
+    fn outgoing(ray_in: &Ray, hit_rec: &HitRecord) -> Option<(Onb, Vec3)> {
+        let frame = hit_rec.frame();
+        let wo = frame.local(-ray_in.dir.normalized());
+        (wo.z() > 0.).then_some((frame, wo))
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<Scatter> {
+        let Some(distribution) = self.distribution else {
+            let unit_direction = ray_in.dir.normalized();
+            let cos_theta = (-unit_direction).dot(hit_rec.normal).max(0.);
+            let scattered = Ray::new(
+                hit_rec.point,
+                unit_direction.reflect(hit_rec.normal),
+                ray_in.time,
+            );
+            let attenuation = fresnel_conductor(cos_theta, self.eta, self.k);
+            return Some(Scatter::Specular(scattered, attenuation));
+        };
+        let (frame, wo) = Self::outgoing(ray_in, hit_rec)?;
+        let Some(wi) = distribution.sample_reflection(wo) else {
+            return Some(Scatter::lost(ray_in, hit_rec));
+        };
+        let (h, f, pdf) = distribution.reflection(wo, wi)?;
+        Some(Scatter::Sampled {
+            ray: Ray::new(hit_rec.point, frame.transform(wi), ray_in.time),
+            weight: f / pdf * fresnel_conductor(wo.dot(h), self.eta, self.k),
+            pdf,
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> Color {
+        let (Some(distribution), Some((frame, wo))) =
+            (self.distribution, Self::outgoing(ray_in, hit_rec))
+        else {
+            return Color::BLACK;
+        };
+        match distribution.reflection(wo, frame.local(dir.normalized())) {
+            Some((h, f, _)) => f * fresnel_conductor(wo.dot(h), self.eta, self.k),
+            None => Color::BLACK,
+        }
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> f64 {
+        let (Some(distribution), Some((frame, wo))) =
+            (self.distribution, Self::outgoing(ray_in, hit_rec))
+        else {
+            return 0.;
+        };
+        distribution
+            .reflection(wo, frame.local(dir.normalized()))
+            .map_or(0., |(_, _, pdf)| pdf)
+    }
+}
+
+// measured indices of refraction of common metals at red, green and blue
+// wavelengths, as (eta, k)
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    Aluminum,
+    Silver,
+}
+
+impl ConductorPreset {
+    pub fn ior(self) -> (Color, Color) {
+        let (eta, k) = match self {
+            ConductorPreset::Gold => ([0.143, 0.374, 1.442], [3.983, 2.385, 1.603]),
+            ConductorPreset::Copper => ([0.200, 0.924, 1.102], [3.912, 2.452, 2.142]),
+            ConductorPreset::Aluminum => ([1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+            ConductorPreset::Silver => ([0.155, 0.117, 0.138], [4.828, 3.122, 2.147]),
+        };
+        (eta.into(), k.into())
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Lambertian {
-    pub albedo: Color,
+    pub albedo: TextureKind,
 }
 
+// a unit normal plus a random unit vector is cosine distributed, so the
+// albedo is all that is left of bsdf * cos / pdf
 impl Material for Lambertian {
-    fn scatter(&self, _: &Ray, hit_rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<Scatter> {
         let mut scatter_direction = hit_rec.normal + Vec3::random_unit_vector();
 
         if scatter_direction.near_zero() {
             scatter_direction = hit_rec.normal;
         }
 
-        Some((Ray::new(hit_rec.point, scatter_direction), self.albedo))
+        Some(Scatter::Sampled {
+            ray: Ray::new(hit_rec.point, scatter_direction, ray_in.time),
+            weight: self.albedo.value(hit_rec.u, hit_rec.v, &hit_rec.point),
+            pdf: self.pdf(ray_in, hit_rec, scatter_direction),
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> Color {
+        self.pdf(ray_in, hit_rec, dir) * self.albedo.value(hit_rec.u, hit_rec.v, &hit_rec.point)
+    }
+
+    fn pdf(&self, _: &Ray, hit_rec: &HitRecord, dir: Vec3) -> f64 {
+        hit_rec.normal.dot(dir.normalized()).max(0.) / PI
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+// glass and water. Rough surfaces spread reflection and refraction over
+// microfacets (Walter et al. 2007), `absorption` tints light by Beer-Lambert's
+// law along the way through the inside.
+#[derive(Clone, Debug)]
 pub struct Dielectric {
     pub refraction_index: f64,
+    // None for a smooth surface
+    pub distribution: Option<Ggx>,
+    pub absorption: Color,
+    // takes over from `refraction_index` for rays with a wavelength
+    pub dispersion: Option<Dispersion>,
 }
 
-impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<(Ray, Color)> {
-        let ri = if hit_rec.front_face {
-            1. / self.refraction_index
-        } else {
-            self.refraction_index
+impl Dielectric {
+    pub fn new(refraction_index: f64) -> Dielectric {
+        Dielectric {
+            refraction_index,
+            distribution: None,
+            absorption: Color::BLACK,
+            dispersion: None,
+        }
+    }
+
+    // ratio of the refraction index on the far side of the surface to the one
+    // the ray arrives from
+    fn eta(&self, ray_in: &Ray, hit_rec: &HitRecord) -> f64 {
+        let refraction_index = match (self.dispersion, ray_in.wavelength) {
+            (Some(dispersion), Some(wavelength)) => dispersion.refraction_index(wavelength),
+            _ => self.refraction_index,
         };
+        if hit_rec.front_face {
+            refraction_index
+        } else {
+            1. / refraction_index
+        }
+    }
+
+    fn smooth_scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Scatter {
+        let ri = 1. / self.eta(ray_in, hit_rec);
         let unit_direction = ray_in.dir.normalized();
         let cos_theta = (-unit_direction).dot(hit_rec.normal).min(1.);
         let sin_theta = (1. - cos_theta * cos_theta).sqrt();
@@ -85,10 +391,438 @@ impl Material for Dielectric {
             unit_direction.refract(hit_rec.normal, ri)
         };
 
-        Some((Ray::new(hit_rec.point, direction), Color::WHITE))
+        Scatter::Specular(
+            Ray::new(hit_rec.point, direction, ray_in.time),
+            Color::WHITE,
+        )
     }
 }
 
+impl Material for Dielectric {
+    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<Scatter> {
+        let Some(distribution) = self.distribution else {
+            return Some(self.smooth_scatter(ray_in, hit_rec));
+        };
+        let frame = hit_rec.frame();
+        let wo = frame.local(-ray_in.dir.normalized());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let eta = self.eta(ray_in, hit_rec);
+        let Some(wi) = distribution.sample_dielectric(eta, wo) else {
+            return Some(Scatter::lost(ray_in, hit_rec));
+        };
+        let (f, pdf) = distribution.dielectric(eta, wo, wi);
+        let weight = if pdf > 0. { f / pdf } else { 0. };
+        Some(Scatter::Sampled {
+            ray: Ray::new(hit_rec.point, frame.transform(wi), ray_in.time),
+            weight: weight * Color::WHITE,
+            pdf,
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> Color {
+        let Some(distribution) = self.distribution else {
+            return Color::BLACK;
+        };
+        let frame = hit_rec.frame();
+        let wo = frame.local(-ray_in.dir.normalized());
+        let wi = frame.local(dir.normalized());
+        distribution.dielectric(self.eta(ray_in, hit_rec), wo, wi).0 * Color::WHITE
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> f64 {
+        let Some(distribution) = self.distribution else {
+            return 0.;
+        };
+        let frame = hit_rec.frame();
+        let wo = frame.local(-ray_in.dir.normalized());
+        let wi = frame.local(dir.normalized());
+        distribution.dielectric(self.eta(ray_in, hit_rec), wo, wi).1
+    }
+
+    fn transmittance(&self, distance: f64) -> Color {
+        beer_lambert(self.absorption, distance)
+    }
+}
+
+// refraction index over the wavelength in micrometers, with the coefficients
+// glass catalogs give for them
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Dispersion {
+    // n = a + b / λ²
+    Cauchy { a: f64, b: f64 },
+    // n² = 1 + Σ b λ² / (λ² - c)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn refraction_index(self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.).powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = b.iter().zip(c).map(|(b, c)| b * l2 / (l2 - c)).sum();
+                (1. + sum).sqrt()
+            }
+        }
+    }
+}
+
+// one material for everything from plastic and metal to glass and car paint,
+// after Disney's principled BSDF (Burley 2012, 2015): Burley diffuse with
+// sheen, a GGX specular lobe going from dielectric to metallic, rough glass
+// and a clearcoat layer on top. Each layer only gets the light the ones above
+// it did not reflect.
+#[derive(Clone, Debug)]
+pub struct Principled {
+    pub base_color: TextureKind,
+    pub metallic: f64,
+    pub roughness: f64,
+    pub anisotropy: f64,
+    // reflectance of the dielectric base at normal incidence, 0.5 is 4%
+    pub specular: f64,
+    pub specular_tint: f64,
+    pub transmission: f64,
+    pub ior: f64,
+    pub clearcoat: f64,
+    pub clearcoat_gloss: f64,
+    pub sheen: f64,
+    pub sheen_tint: f64,
+    pub absorption: Color,
+}
+
+// the lobes of a principled material at one point, for light leaving toward
+// `wo` in the surface frame
+struct Lobes {
+    frame: Onb,
+    wo: Vec3,
+    base: Color,
+    sheen: Color,
+    roughness: f64,
+    specular: Ggx,
+    // Schlick's approximation from `f0` at normal incidence to `f90`
+    f0: Color,
+    f90: f64,
+    eta: f64,
+    transmission_tint: Color,
+    clearcoat: Ggx,
+    // scales of the diffuse, specular, transmission and clearcoat lobes and
+    // the probabilities of sampling them
+    weights: [f64; 4],
+    probabilities: [f64; 4],
+}
+
+impl Principled {
+    fn lobes(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<Lobes> {
+        let frame = hit_rec.frame();
+        let wo = frame.local(-ray_in.dir.normalized());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let base = self.base_color.value(hit_rec.u, hit_rec.v, &hit_rec.point);
+        let luminance = base.luminance();
+        let tint = if luminance > 0. {
+            base / luminance
+        } else {
+            Color::WHITE
+        };
+        let tinted = |amount: f64| (1. - amount) * Color::WHITE + amount * tint;
+        let gloss = self.clearcoat_gloss;
+
+        let mut lobes = Lobes {
+            frame,
+            wo,
+            base,
+            sheen: self.sheen * tinted(self.sheen_tint),
+            roughness: self.roughness,
+            specular: Ggx::new(self.roughness, self.anisotropy),
+            f0: Color::BLACK,
+            f90: 0.,
+            eta: 1. / self.ior,
+            transmission_tint: Color::WHITE,
+            clearcoat: Ggx::new((0.1 * (1. - gloss) + 0.001 * gloss).sqrt(), 0.),
+            weights: [0., 0., 1., 0.],
+            probabilities: [0., 0., 1., 0.],
+        };
+        // light that got in through the glass lobe leaves through a plain
+        // rough boundary
+        if !hit_rec.front_face && self.transmission > 0. {
+            return Some(lobes);
+        }
+
+        let metallic = self.metallic;
+        let dielectric = (1. - metallic) * (1. - self.transmission);
+        let specular_f0 = 0.08 * self.specular * tinted(self.specular_tint);
+        lobes.f0 = metallic * base + dielectric * specular_f0;
+        lobes.f90 = metallic + dielectric;
+        lobes.eta = self.ior;
+        lobes.transmission_tint = base;
+
+        let specular = schlick(specular_f0.luminance(), wo.z());
+        let clearcoat = 0.25 * self.clearcoat * schlick(0.04, wo.z());
+        let below = 1. - clearcoat;
+        lobes.weights = [
+            below * dielectric * (1. - specular),
+            below,
+            below * (1. - metallic) * self.transmission,
+            0.25 * self.clearcoat,
+        ];
+        let probabilities = [
+            lobes.weights[0],
+            below * (metallic + dielectric * specular),
+            lobes.weights[2],
+            clearcoat,
+        ];
+        let total: f64 = probabilities.iter().sum();
+        if total <= 0. {
+            return None;
+        }
+        lobes.probabilities = probabilities.map(|p| p / total);
+        Some(lobes)
+    }
+}
+
+impl Lobes {
+    fn sample(&self) -> Option<Vec3> {
+        let wo = self.wo;
+        let mut choice = random_f64();
+        let lobe = self
+            .probabilities
+            .iter()
+            .position(|&p| {
+                choice -= p;
+                choice < 0.
+            })
+            .unwrap_or(3);
+        match lobe {
+            0 => {
+                let wi = Vec3::new(0., 0., 1.) + Vec3::random_unit_vector();
+                (!wi.near_zero() && wi.z() > 0.).then(|| wi.normalized())
+            }
+            1 => self.specular.sample_reflection(wo),
+            2 => self.specular.sample_dielectric(self.eta, wo),
+            _ => self.clearcoat.sample_reflection(wo),
+        }
+    }
+
+    // bsdf times cosine and the pdf of `sample` for the local direction `wi`
+    fn eval(&self, wi: Vec3) -> (Color, f64) {
+        let wo = self.wo;
+        let [diffuse, specular, transmission, clearcoat] = self.weights;
+        let p = self.probabilities;
+        let mut f = Color::BLACK;
+        let mut pdf = 0.;
+
+        if wi.z() > 0. && diffuse > 0. {
+            let cos_d = wi.dot((wo + wi).normalized());
+            let fd90 = 0.5 + 2. * self.roughness * cos_d * cos_d;
+            let retro = |cos: f64| 1. + (fd90 - 1.) * (1. - cos).powi(5);
+            let burley = retro(wi.z()) * retro(wo.z()) / PI * self.base;
+            let sheen = (1. - cos_d).powi(5) * self.sheen;
+            f += diffuse * wi.z() * (burley + sheen);
+            pdf += p[0] * wi.z() / PI;
+        }
+        if specular > 0. {
+            if let Some((h, g, lobe_pdf)) = self.specular.reflection(wo, wi) {
+                let c = (1. - wo.dot(h)).powi(5);
+                let fresnel = self.f0 + c * (self.f90 * Color::WHITE - self.f0);
+                f += specular * g * fresnel;
+                pdf += p[1] * lobe_pdf;
+            }
+        }
+        if transmission > 0. {
+            let (g, lobe_pdf) = self.specular.dielectric(self.eta, wo, wi);
+            let tint = if wi.z() < 0. {
+                self.transmission_tint
+            } else {
+                Color::WHITE
+            };
+            f += transmission * g * tint;
+            pdf += p[2] * lobe_pdf;
+        }
+        if clearcoat > 0. {
+            if let Some((h, g, lobe_pdf)) = self.clearcoat.reflection(wo, wi) {
+                f += clearcoat * g * schlick(0.04, wo.dot(h)) * Color::WHITE;
+                pdf += p[3] * lobe_pdf;
+            }
+        }
+        (f, pdf)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<Scatter> {
+        let lobes = self.lobes(ray_in, hit_rec)?;
+        let Some(wi) = lobes.sample() else {
+            return Some(Scatter::lost(ray_in, hit_rec));
+        };
+        let (f, pdf) = lobes.eval(wi);
+        Some(Scatter::Sampled {
+            ray: Ray::new(hit_rec.point, lobes.frame.transform(wi), ray_in.time),
+            weight: if pdf > 0. { 1. / pdf * f } else { Color::BLACK },
+            pdf,
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> Color {
+        self.lobes(ray_in, hit_rec).map_or(Color::BLACK, |lobes| {
+            lobes.eval(lobes.frame.local(dir.normalized())).0
+        })
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> f64 {
+        self.lobes(ray_in, hit_rec).map_or(0., |lobes| {
+            lobes.eval(lobes.frame.local(dir.normalized())).1
+        })
+    }
+
+    fn transmittance(&self, distance: f64) -> Color {
+        beer_lambert(self.absorption, distance)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffuseLight {
+    pub emit: TextureKind,
+    pub two_sided: bool,
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _: &Ray, _: &HitRecord) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _: &Ray, hit_rec: &HitRecord) -> Color {
+        if self.two_sided || hit_rec.front_face {
+            self.emit.value(hit_rec.u, hit_rec.v, &hit_rec.point)
+        } else {
+            Color::BLACK
+        }
+    }
+}
+
+// scatters uniformly in all directions, the phase function of fog and smoke
+#[derive(Clone, Debug)]
+pub struct Isotropic {
+    pub albedo: TextureKind,
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<Scatter> {
+        Some(Scatter::Sampled {
+            ray: Ray::new(hit_rec.point, Vec3::random_unit_vector(), ray_in.time),
+            weight: self.albedo.value(hit_rec.u, hit_rec.v, &hit_rec.point),
+            pdf: 1. / (4. * PI),
+        })
+    }
+
+    fn eval(&self, _: &Ray, hit_rec: &HitRecord, _: Vec3) -> Color {
+        self.albedo.value(hit_rec.u, hit_rec.v, &hit_rec.point) / (4. * PI)
+    }
+
+    fn pdf(&self, _: &Ray, _: &HitRecord, _: Vec3) -> f64 {
+        1. / (4. * PI)
+    }
+}
+
+// tilts the shading normal of the material underneath, which sees the
+// surface as if it had the detail of the map
+#[derive(Clone, Debug)]
+pub struct Bumped {
+    pub material: MaterialKind,
+    pub bump: Bump,
+}
+
+#[derive(Clone, Debug)]
+pub enum Bump {
+    // tangent space normals encoded as colors, x along the tangent and y along
+    // the bitangent, with their tilt scaled by `strength`
+    NormalMap { texture: TextureKind, strength: f64 },
+    // heights over the surface, the luminance of the texture times `scale`
+    HeightMap { texture: TextureKind, scale: f64 },
+}
+
+impl Bump {
+    fn apply<'a>(&self, hit_rec: &HitRecord<'a>) -> HitRecord<'a> {
+        let n = hit_rec.outward_normal();
+        let (u, v, p) = (hit_rec.u, hit_rec.v, hit_rec.point);
+        let normal = match self {
+            Bump::NormalMap { texture, strength } => {
+                let c = texture.value(u, v, &p);
+                let frame = hit_rec.frame_around(n);
+                // mirrored texture coordinates flip the bitangent
+                let handedness = frame.v.dot(hit_rec.bitangent).signum();
+                frame.transform(Vec3::new(
+                    strength * (2. * c.x() - 1.),
+                    handedness * strength * (2. * c.y() - 1.),
+                    (2. * c.z() - 1.).max(1e-3),
+                ))
+            }
+            Bump::HeightMap { texture, scale } => {
+                // finite differences along the texture coordinates, moving
+                // the point along with them for solid textures
+                const DELTA: f64 = 5e-4;
+                let height = |du: f64, dv: f64| {
+                    let point = p + du * hit_rec.tangent + dv * hit_rec.bitangent;
+                    scale * texture.value(u + du, v + dv, &point).luminance()
+                };
+                let h = height(0., 0.);
+                let dpdu = hit_rec.tangent + (height(DELTA, 0.) - h) / DELTA * n;
+                let dpdv = hit_rec.bitangent + (height(0., DELTA) - h) / DELTA * n;
+                let normal = dpdu.cross(dpdv);
+                if normal.dot(n) < 0. {
+                    -normal
+                } else {
+                    normal
+                }
+            }
+        };
+        if normal.near_zero() || !normal.x().is_finite() {
+            return *hit_rec;
+        }
+        hit_rec.with_shading_normal(normal.normalized())
+    }
+}
+
+impl Material for Bumped {
+    fn scatter(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Option<Scatter> {
+        self.material.scatter(ray_in, &self.bump.apply(hit_rec))
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> Color {
+        self.material.eval(ray_in, &self.bump.apply(hit_rec), dir)
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_rec: &HitRecord, dir: Vec3) -> f64 {
+        self.material.pdf(ray_in, &self.bump.apply(hit_rec), dir)
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_rec: &HitRecord) -> Color {
+        self.material.emitted(ray_in, &self.bump.apply(hit_rec))
+    }
+
+    fn transmittance(&self, distance: f64) -> Color {
+        self.material.transmittance(distance)
+    }
+}
+
+fn schlick(f0: f64, cosine: f64) -> f64 {
+    f0 + (1. - f0) * (1. - cosine).powi(5)
+}
+
+fn beer_lambert(absorption: Color, distance: f64) -> Color {
+    let a = absorption;
+    Color::new(
+        (-a.x() * distance).exp(),
+        (-a.y() * distance).exp(),
+        (-a.z() * distance).exp(),
+    )
+}
+
 pub fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
     let r0 = (1. - refraction_index) / (1. + refraction_index);
     let r0_2 = r0 * r0;
@@ -0,0 +1,333 @@
+use std::{f64::consts::PI, fs, io, path::Path, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, HittableKind},
+    interval::Interval,
+    material::MaterialKind,
+    noise::{Fractal, Perlin},
+    onb::Onb,
+    random::random_f64,
+    ray::Ray,
+    vec3::{Color, Point, Vec3},
+};
+
+// fog filling a closed boundary, rays inside scatter after an exponentially
+// distributed distance; the phase function is given by the material
+pub struct ConstantMedium {
+    boundary: Arc<HittableKind>,
+    neg_inv_density: f64,
+    phase_function: MaterialKind,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<HittableKind>,
+        density: f64,
+        phase_function: MaterialKind,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1. / density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        // the boundary is crossed twice, even if the ray starts inside
+        let entry = self.boundary.hit(ray, Interval::UNIVERSE)?;
+        let exit = self
+            .boundary
+            .hit(ray, Interval::new(entry.t + 0.0001, f64::INFINITY))?;
+
+        let t_min = entry.t.max(ray_t.min).max(0.);
+        let t_max = exit.t.min(ray_t.max);
+        if t_min >= t_max {
+            return None;
+        }
+
+        let ray_length = ray.dir.length();
+        let distance_inside = (t_max - t_min) * ray_length;
+        let hit_distance = self.neg_inv_density * random_f64().ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_min + hit_distance / ray_length;
+
+        // normal and side mean nothing inside a volume
+        Some(HitRecord {
+            point: ray.at(t),
+            normal: Vec3::new(1., 0., 0.),
+            geometric_normal: Vec3::new(1., 0., 0.),
+            tangent: Vec3::new(0., 1., 0.),
+            bitangent: Vec3::new(0., 0., 1.),
+            t,
+            u: 0.,
+            v: 0.,
+            front_face: true,
+            material: &self.phase_function,
+            medium: None,
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+// density of a heterogeneous medium before scaling, in [0, 1] for noise
+#[derive(Clone, Debug)]
+pub enum DensityField {
+    Uniform,
+    Grid(VoxelGrid),
+    Noise {
+        perlin: Arc<Perlin>,
+        scale: f64,
+        fractal: Fractal,
+    },
+}
+
+impl DensityField {
+    fn value(&self, p: &Point) -> f64 {
+        match self {
+            DensityField::Uniform => 1.,
+            DensityField::Grid(grid) => grid.value(p),
+            DensityField::Noise {
+                perlin,
+                scale,
+                fractal,
+            } => (0.5 * (1. + perlin.fbm(&(*scale * *p), fractal))).clamp(0., 1.),
+        }
+    }
+
+    fn max(&self) -> f64 {
+        match self {
+            DensityField::Uniform | DensityField::Noise { .. } => 1.,
+            DensityField::Grid(grid) => grid.max,
+        }
+    }
+}
+
+// one density per cell of a `resolution` grid dividing `bounds`, sampled at
+// the cell centers and fading to zero over the outer half cells
+#[derive(Clone, Debug)]
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    bounds: Aabb,
+    values: Arc<[f32]>,
+    max: f64,
+}
+
+impl VoxelGrid {
+    // raw little-endian f32 values, x varying fastest and z slowest
+    pub fn load(path: &Path, resolution: [usize; 3], bounds: Aabb) -> io::Result<VoxelGrid> {
+        let bytes = fs::read(path)?;
+        let count = resolution.iter().product::<usize>();
+        if bytes.len() != 4 * count {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "expected {} bytes for a {}x{}x{} grid, found {}",
+                    4 * count,
+                    resolution[0],
+                    resolution[1],
+                    resolution[2],
+                    bytes.len()
+                ),
+            ));
+        }
+
+        let values: Arc<[f32]> = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]).max(0.))
+            .collect();
+        let max = values.iter().fold(0f32, |max, &v| max.max(v)) as f64;
+        Ok(VoxelGrid {
+            resolution,
+            bounds,
+            values,
+            max,
+        })
+    }
+
+    fn voxel(&self, [x, y, z]: [i64; 3]) -> f64 {
+        let [nx, ny, nz] = self.resolution.map(|n| n as i64);
+        if x < 0 || y < 0 || z < 0 || x >= nx || y >= ny || z >= nz {
+            return 0.;
+        }
+        self.values[((z * ny + y) * nx + x) as usize] as f64
+    }
+
+    // trilinear between voxel centers
+    fn value(&self, p: &Point) -> f64 {
+        let mut cell = [0; 3];
+        let mut frac = [0.; 3];
+        for axis in 0..3 {
+            let extent = self.bounds.axis_interval(axis);
+            let x = (p[axis] - extent.min) / extent.size() * self.resolution[axis] as f64 - 0.5;
+            cell[axis] = x.floor() as i64;
+            frac[axis] = x - x.floor();
+        }
+
+        let mut accum = 0.;
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let mut weight = 1.;
+            for axis in 0..3 {
+                weight *= if offset[axis] == 1 {
+                    frac[axis]
+                } else {
+                    1. - frac[axis]
+                };
+            }
+            if weight > 0. {
+                accum += weight * self.voxel([0, 1, 2].map(|a| cell[a] + offset[a] as i64));
+            }
+        }
+        accum
+    }
+}
+
+// what happened to a ray travelling through a medium up to the next surface
+pub enum Interaction {
+    Scattered(Point),
+    Transmitted(f64),
+}
+
+// spatially varying fog: extinction is `density` times the field, scattering
+// follows a Henyey-Greenstein phase function with anisotropy `g` (positive
+// scatters forward)
+#[derive(Clone, Debug)]
+pub struct Medium {
+    pub field: DensityField,
+    pub density: f64,
+    pub albedo: Color,
+    pub g: f64,
+}
+
+impl Medium {
+    fn majorant(&self) -> f64 {
+        self.density * self.field.max()
+    }
+
+    fn extinction(&self, p: &Point) -> f64 {
+        self.density * self.field.value(p)
+    }
+
+    // tentative collisions are drawn against the majorant and kept with
+    // probability extinction / majorant. Media that only absorb are not
+    // sampled at all, ratio tracking weighs the ray by its transmittance
+    // instead, which is less noisy than terminating paths.
+    pub fn interact(&self, ray: &Ray, t_max: f64) -> Interaction {
+        let majorant = self.majorant();
+        if majorant <= 0. {
+            return Interaction::Transmitted(1.);
+        }
+
+        let step = |t: f64| t - (1. - random_f64()).ln() / (majorant * ray.dir.length());
+        if self.albedo.near_zero() {
+            return Interaction::Transmitted(self.transmittance(ray, t_max));
+        }
+
+        let mut t = 0.;
+        loop {
+            t = step(t);
+            if t >= t_max {
+                return Interaction::Transmitted(1.);
+            }
+            let point = ray.at(t);
+            if random_f64() * majorant < self.extinction(&point) {
+                return Interaction::Scattered(point);
+            }
+        }
+    }
+
+    // samples the direction a ray travelling along `dir` scatters into
+    pub fn sample_phase(&self, dir: Vec3) -> Vec3 {
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1. - 2. * random_f64()
+        } else {
+            let s = (1. - g * g) / (1. - g + 2. * g * random_f64());
+            (1. + g * g - s * s) / (2. * g)
+        };
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * random_f64();
+
+        Onb::new(dir).transform(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+
+    // density of scattering from `dir` into `scattered`, sample_phase draws
+    // exactly this distribution
+    pub fn phase(&self, dir: Vec3, scattered: Vec3) -> f64 {
+        let cos_theta = dir.normalized().dot(scattered.normalized());
+        let denom = 1. + self.g * self.g - 2. * self.g * cos_theta;
+        (1. - self.g * self.g) / (4. * PI * denom * denom.sqrt())
+    }
+
+    // ratio tracking estimate of the transmittance up to `t_max`
+    pub fn transmittance(&self, ray: &Ray, t_max: f64) -> f64 {
+        let majorant = self.majorant();
+        if majorant <= 0. {
+            return 1.;
+        }
+
+        if let DensityField::Uniform = self.field {
+            return (-self.density * t_max * ray.dir.length()).exp();
+        }
+
+        let mut transmittance = 1.;
+        let mut t = 0.;
+        loop {
+            t -= (1. - random_f64()).ln() / (majorant * ray.dir.length());
+            if t >= t_max {
+                return transmittance;
+            }
+            transmittance *= 1. - self.extinction(&ray.at(t)) / majorant;
+        }
+    }
+}
+
+// the surface of `boundary` encloses `medium`. Without a material of its own
+// the surface is invisible and rays pass straight into the medium.
+pub struct Volume {
+    boundary: Arc<HittableKind>,
+    medium: Medium,
+    material: MaterialKind,
+}
+
+impl Volume {
+    pub fn new(
+        boundary: Arc<HittableKind>,
+        medium: Medium,
+        material: Option<MaterialKind>,
+    ) -> Volume {
+        Volume {
+            boundary,
+            medium,
+            material: material.unwrap_or(MaterialKind::Interface),
+        }
+    }
+}
+
+impl Hittable for Volume {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let hit_rec = self.boundary.hit(ray, ray_t)?;
+        Some(HitRecord {
+            material: &self.material,
+            medium: Some(&self.medium),
+            ..hit_rec
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
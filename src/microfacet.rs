@@ -0,0 +1,206 @@
+use std::f64::consts::PI;
+
+use crate::{
+    random::random_f64,
+    vec3::{Color, Vec3},
+};
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals, in a local frame
+// where the surface normal is +z and alpha_x is the roughness along +x
+#[derive(Clone, Copy, Debug)]
+pub struct Ggx {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl Ggx {
+    // artists' roughness is squared into alpha, anisotropy in [0, 1]
+    // stretches the highlight along x
+    pub fn new(roughness: f64, anisotropy: f64) -> Ggx {
+        let aspect = (1. - 0.9 * anisotropy).sqrt();
+        let alpha = roughness * roughness;
+        Ggx {
+            alpha_x: (alpha / aspect).max(1e-4),
+            alpha_y: (alpha * aspect).max(1e-4),
+        }
+    }
+
+    pub fn d(&self, h: Vec3) -> f64 {
+        if h.z() <= 0. {
+            return 0.;
+        }
+        let x = h.x() / self.alpha_x;
+        let y = h.y() / self.alpha_y;
+        let e = x * x + y * y + h.z() * h.z();
+        1. / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    fn lambda(&self, w: Vec3) -> f64 {
+        let z2 = w.z() * w.z();
+        if z2 == 0. {
+            return f64::INFINITY;
+        }
+        let ax = self.alpha_x * w.x();
+        let ay = self.alpha_y * w.y();
+        0.5 * ((1. + (ax * ax + ay * ay) / z2).sqrt() - 1.)
+    }
+
+    // Smith masking of the microsurface seen from `w`
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1. / (1. + self.lambda(w))
+    }
+
+    // height correlated masking and shadowing
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // density of microfacet normals visible from `wo`
+    pub fn visible_pdf(&self, wo: Vec3, h: Vec3) -> f64 {
+        if wo.z() <= 0. {
+            return 0.;
+        }
+        self.g1(wo) * wo.dot(h).max(0.) * self.d(h) / wo.z()
+    }
+
+    // samples a normal from the visible ones (Heitz 2018), `wo` must be above
+    // the surface
+    pub fn sample_visible(&self, wo: Vec3) -> Vec3 {
+        let vh = Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).normalized();
+
+        let len_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len_squared > 0. {
+            Vec3::new(-vh.y(), vh.x(), 0.) / len_squared.sqrt()
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = vh.cross(t1);
+
+        let r = random_f64().sqrt();
+        let phi = 2. * PI * random_f64();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z());
+        let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1. - p1 * p1 - p2 * p2).max(0.).sqrt() * vh;
+
+        Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        )
+        .normalized()
+    }
+
+    // reflection off the microfacets for local directions on the side of the
+    // normal: the half vector, bsdf times cosine without the Fresnel factor
+    // and the pdf of sample_reflection
+    pub fn reflection(&self, wo: Vec3, wi: Vec3) -> Option<(Vec3, f64, f64)> {
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return None;
+        }
+        let h = (wo + wi).normalized();
+        let f = self.d(h) * self.g2(wo, wi) / (4. * wo.z());
+        Some((h, f, self.visible_pdf(wo, h) / (4. * wo.dot(h))))
+    }
+
+    // mirrors `wo` on a visible microfacet, None for rays sent into the
+    // surface, which are lost
+    pub fn sample_reflection(&self, wo: Vec3) -> Option<Vec3> {
+        let wi = (-wo).reflect(self.sample_visible(wo));
+        (wi.z() > 0.).then_some(wi)
+    }
+
+    // bsdf times cosine and pdf of a rough boundary between dielectrics (Walter
+    // et al. 2007), with `eta` the ratio of the refraction index below the
+    // surface to the one above and `wo` above it. Radiance is not rescaled by
+    // eta squared on the way in or out, as the two cancel for rays that leave
+    // the object again.
+    pub fn dielectric(&self, eta: f64, wo: Vec3, wi: Vec3) -> (f64, f64) {
+        if wo.z() <= 0. || wi.z() == 0. {
+            return (0., 0.);
+        }
+        let reflect = wi.z() > 0.;
+        let etap = if reflect { 1. } else { eta };
+        let mut wm = (etap * wi + wo).normalized();
+        if wm.z() < 0. {
+            wm = -wm;
+        }
+        // microfacets seen from behind by either direction
+        if wm.dot(wi) * wi.z() <= 0. || wm.dot(wo) <= 0. {
+            return (0., 0.);
+        }
+
+        let fresnel = fresnel_dielectric(wo.dot(wm), eta);
+        let visible = self.visible_pdf(wo, wm);
+        let d_g = self.d(wm) * self.g2(wo, wi);
+        if reflect {
+            let f = fresnel * d_g / (4. * wo.z());
+            (f, fresnel * visible / (4. * wo.dot(wm)))
+        } else {
+            let denom = wi.dot(wm) + wo.dot(wm) / etap;
+            let jacobian = wi.dot(wm).abs() / (denom * denom);
+            let f = (1. - fresnel) * d_g * wo.dot(wm) * jacobian / wo.z();
+            (f, (1. - fresnel) * visible * jacobian)
+        }
+    }
+
+    // reflects or refracts by the Fresnel factor of a visible microfacet, None
+    // for reflections into the surface and refractions out of it
+    pub fn sample_dielectric(&self, eta: f64, wo: Vec3) -> Option<Vec3> {
+        let wm = self.sample_visible(wo);
+        let reflect = random_f64() < fresnel_dielectric(wo.dot(wm), eta);
+        let wi = if reflect {
+            (-wo).reflect(wm)
+        } else {
+            refract(wo, wm, eta)
+        };
+        (reflect == (wi.z() > 0.)).then_some(wi)
+    }
+}
+
+// refracts the unit direction `wo`, pointing away from the surface, through
+// a microfacet with normal `n` on its side; never called under total internal
+// reflection, where the Fresnel factor is 1
+fn refract(wo: Vec3, n: Vec3, eta: f64) -> Vec3 {
+    let cos_i = n.dot(wo);
+    let sin2_t = (1. - cos_i * cos_i).max(0.) / (eta * eta);
+    let cos_t = (1. - sin2_t).max(0.).sqrt();
+    -wo / eta + (cos_i / eta - cos_t) * n
+}
+
+// reflectance of the boundary to a medium with `eta` times the refraction
+// index of the incident side, 1 under total internal reflection
+pub fn fresnel_dielectric(cos_theta: f64, eta: f64) -> f64 {
+    let sin2_t = (1. - cos_theta * cos_theta).max(0.) / (eta * eta);
+    if sin2_t >= 1. {
+        return 1.;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    let r_parallel = (eta * cos_theta - cos_t) / (eta * cos_theta + cos_t);
+    let r_perpendicular = (cos_theta - eta * cos_t) / (cos_theta + eta * cos_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+// reflectance of a conductor with complex index of refraction eta + ik for
+// unpolarized light, per channel
+pub fn fresnel_conductor(cos_theta: f64, eta: Color, k: Color) -> Color {
+    let channel = |eta: f64, k: f64| {
+        let c2 = cos_theta * cos_theta;
+        let s2 = 1. - c2;
+        let t0 = eta * eta - k * k - s2;
+        let a2b2 = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+        let t1 = a2b2 + c2;
+        let a = (0.5 * (a2b2 + t0)).max(0.).sqrt();
+        let t2 = 2. * cos_theta * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = c2 * a2b2 + s2 * s2;
+        let t4 = t2 * s2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    };
+    Color::new(
+        channel(eta.x(), k.x()),
+        channel(eta.y(), k.y()),
+        channel(eta.z(), k.z()),
+    )
+}
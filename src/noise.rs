@@ -0,0 +1,151 @@
+use std::array;
+
+use serde::Deserialize;
+
+use crate::vec3::{Point, Vec3};
+
+const POINT_COUNT: usize = 256;
+
+// sums `octaves` layers of noise, each `lacunarity` times the frequency and
+// `gain` times the amplitude of the one before; `smooth` picks Hermite over
+// plain trilinear weights
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fractal {
+    pub octaves: u32,
+    pub lacunarity: f64,
+    pub gain: f64,
+    pub smooth: bool,
+}
+
+impl Default for Fractal {
+    fn default() -> Self {
+        Fractal {
+            octaves: 7,
+            lacunarity: 2.,
+            gain: 0.5,
+            smooth: true,
+        }
+    }
+}
+
+// gradient noise on an integer lattice, cells are hashed through three
+// permutation tables; the same hash places the feature points of Worley noise.
+// Everything is drawn from its own generator so a seed always gives the same
+// noise, regardless of the global random state.
+#[derive(Debug)]
+pub struct Perlin {
+    gradients: [Vec3; POINT_COUNT],
+    points: [Point; POINT_COUNT],
+    perm_x: [u8; POINT_COUNT],
+    perm_y: [u8; POINT_COUNT],
+    perm_z: [u8; POINT_COUNT],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = fastrand::Rng::with_seed(seed);
+
+        let gradients = array::from_fn(|_| loop {
+            let v = Vec3::new(
+                2. * rng.f64() - 1.,
+                2. * rng.f64() - 1.,
+                2. * rng.f64() - 1.,
+            );
+            let len_sq = v.length_squared();
+            if 1e-160 < len_sq && len_sq <= 1. {
+                break v / len_sq.sqrt();
+            }
+        });
+        let points = array::from_fn(|_| Point::new(rng.f64(), rng.f64(), rng.f64()));
+        let mut permute = || {
+            let mut perm = array::from_fn(|i| i as u8);
+            rng.shuffle(&mut perm);
+            perm
+        };
+
+        Perlin {
+            gradients,
+            points,
+            perm_x: permute(),
+            perm_y: permute(),
+            perm_z: permute(),
+        }
+    }
+
+    fn hash(&self, i: i64, j: i64, k: i64) -> usize {
+        (self.perm_x[(i & 255) as usize]
+            ^ self.perm_y[(j & 255) as usize]
+            ^ self.perm_z[(k & 255) as usize]) as usize
+    }
+
+    // roughly in [-1, 1], trilinear interpolation of the lattice gradients,
+    // Hermite smoothing the weights hides the lattice at cell boundaries
+    pub fn noise(&self, p: &Point, smooth: bool) -> f64 {
+        let (fx, fy, fz) = (p.x().floor(), p.y().floor(), p.z().floor());
+        let (u, v, w) = (p.x() - fx, p.y() - fy, p.z() - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        let fade = |t: f64| if smooth { t * t * (3. - 2. * t) } else { t };
+        let (uu, vv, ww) = (fade(u), fade(v), fade(w));
+
+        let mut accum = 0.;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.gradients[self.hash(i + di, j + dj, k + dk)];
+                    let (di, dj, dk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - di, v - dj, w - dk);
+                    accum += (di * uu + (1. - di) * (1. - uu))
+                        * (dj * vv + (1. - dj) * (1. - vv))
+                        * (dk * ww + (1. - dk) * (1. - ww))
+                        * gradient.dot(weight);
+                }
+            }
+        }
+        accum
+    }
+
+    // fractional Brownian motion, signed
+    pub fn fbm(&self, p: &Point, fractal: &Fractal) -> f64 {
+        self.octaves(p, fractal, |n| n)
+    }
+
+    // fBm of the absolute noise, always positive
+    pub fn turbulence(&self, p: &Point, fractal: &Fractal) -> f64 {
+        self.octaves(p, fractal, f64::abs)
+    }
+
+    fn octaves(&self, p: &Point, fractal: &Fractal, shape: impl Fn(f64) -> f64) -> f64 {
+        let mut accum = 0.;
+        let mut p = *p;
+        let mut amplitude = 1.;
+        for _ in 0..fractal.octaves {
+            accum += amplitude * shape(self.noise(&p, fractal.smooth));
+            amplitude *= fractal.gain;
+            p = fractal.lacunarity * p;
+        }
+        accum
+    }
+
+    // distance to the closest feature point, one point per unit cell
+    pub fn worley(&self, p: &Point) -> f64 {
+        let (i, j, k) = (
+            p.x().floor() as i64,
+            p.y().floor() as i64,
+            p.z().floor() as i64,
+        );
+
+        let mut closest = f64::INFINITY;
+        for ci in i - 1..=i + 1 {
+            for cj in j - 1..=j + 1 {
+                for ck in k - 1..=k + 1 {
+                    let cell = Point::new(ci as f64, cj as f64, ck as f64);
+                    let feature = cell + self.points[self.hash(ci, cj, ck)];
+                    closest = closest.min((feature - *p).length_squared());
+                }
+            }
+        }
+        closest.sqrt()
+    }
+}
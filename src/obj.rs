@@ -0,0 +1,479 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    hittable::{HittableKind, HittableList},
+    material::{Dielectric, DiffuseLight, Lambertian, MaterialKind, Metal},
+    texture::{ImageTexture, TextureKind},
+    triangle::{Mesh, MeshFace, TriangleMesh},
+    vec3::{Color, Point, Vec3},
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Syntax {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Syntax { .. } => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct MtlMaterial {
+    kd: Color,
+    map_kd: Option<ImageTexture>,
+    ks: Color,
+    ke: Color,
+    ns: f64,
+    ni: Option<f64>,
+    d: f64,
+    illum: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            kd: Color::new(0.8, 0.8, 0.8),
+            map_kd: None,
+            ks: Color::BLACK,
+            ke: Color::BLACK,
+            ns: 0.,
+            ni: None,
+            d: 1.,
+            illum: 1,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // emissive materials become lights, illum models 4, 6, 7 and 9 are the
+    // transparent ones, 3, 5 and 8 turn on mirror reflection; everything else
+    // is treated as plain diffuse
+    fn to_material(&self) -> MaterialKind {
+        let diffuse = match &self.map_kd {
+            Some(image) => TextureKind::Image(image.clone()),
+            None => self.kd.into(),
+        };
+
+        if !self.ke.near_zero() {
+            MaterialKind::DiffuseLight(DiffuseLight {
+                emit: self.ke.into(),
+                two_sided: false,
+            })
+        } else if self.d < 1. || matches!(self.illum, 4 | 6 | 7 | 9) {
+            MaterialKind::Dielectric(Dielectric::new(self.ni.unwrap_or(1.5)))
+        } else if matches!(self.illum, 3 | 5 | 8) {
+            // map the Phong exponent onto a roughness in [0, 1]
+            let fuzz = (2. / (self.ns.max(0.) + 2.)).sqrt();
+            let albedo = if self.ks.near_zero() {
+                diffuse
+            } else {
+                self.ks.into()
+            };
+            MaterialKind::Metal(Metal { albedo, fuzz })
+        } else {
+            MaterialKind::Lambertian(Lambertian { albedo: diffuse })
+        }
+    }
+}
+
+struct Parser<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> ObjError {
+        ObjError::Syntax {
+            path: self.path.to_owned(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn f64(&self, token: Option<&str>, what: &str) -> Result<f64, ObjError> {
+        let token = token.ok_or_else(|| self.error(format!("missing {}", what)))?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} `{}`", what, token)))
+    }
+
+    fn vec3<'t>(&self, tokens: &mut impl Iterator<Item = &'t str>) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(
+            self.f64(tokens.next(), "x coordinate")?,
+            self.f64(tokens.next(), "y coordinate")?,
+            self.f64(tokens.next(), "z coordinate")?,
+        ))
+    }
+
+    // OBJ indices are 1-based, negative ones count back from the last element
+    fn index(&self, token: &str, len: usize, what: &str) -> Result<usize, ObjError> {
+        let i: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} index `{}`", what, token)))?;
+        let resolved = if i > 0 { i - 1 } else { len as i64 + i };
+        if i == 0 || resolved < 0 || resolved >= len as i64 {
+            return Err(self.error(format!("{} index {} out of range", what, i)));
+        }
+        Ok(resolved as usize)
+    }
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+fn load_mtl(path: &Path, materials: &mut HashMap<String, MtlMaterial>) -> Result<(), ObjError> {
+    let source = read(path)?;
+    let mut parser = Parser { path, line: 0 };
+    let mut current: Option<String> = None;
+
+    for (n, line) in source.lines().enumerate() {
+        parser.line = n + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        if keyword.starts_with('#') {
+            continue;
+        }
+
+        if keyword == "newmtl" {
+            let name = tokens
+                .next()
+                .ok_or_else(|| parser.error("missing material name"))?;
+            materials.insert(name.to_owned(), MtlMaterial::default());
+            current = Some(name.to_owned());
+            continue;
+        }
+
+        let known = ["Kd", "Ks", "Ke", "Ns", "Ni", "d", "Tr", "illum", "map_Kd"];
+        if !known.contains(&keyword) {
+            continue;
+        }
+        let material = current
+            .as_ref()
+            .and_then(|name| materials.get_mut(name))
+            .ok_or_else(|| parser.error(format!("`{}` before any `newmtl`", keyword)))?;
+
+        match keyword {
+            "Kd" => material.kd = parser.vec3(&mut tokens)?,
+            "Ks" => material.ks = parser.vec3(&mut tokens)?,
+            "Ke" => material.ke = parser.vec3(&mut tokens)?,
+            "Ns" => material.ns = parser.f64(tokens.next(), "specular exponent")?,
+            "Ni" => material.ni = Some(parser.f64(tokens.next(), "refraction index")?),
+            "d" => material.d = parser.f64(tokens.next(), "dissolve")?,
+            "Tr" => material.d = 1. - parser.f64(tokens.next(), "transparency")?,
+            "map_Kd" => {
+                // options such as `-s` come first, the file name is last
+                let name = tokens
+                    .last()
+                    .ok_or_else(|| parser.error("missing texture file"))?;
+                let texture = path.parent().unwrap_or(Path::new("")).join(name);
+                let image = ImageTexture::load(&texture, None).map_err(|source| {
+                    parser.error(format!(
+                        "cannot load texture {}: {}",
+                        texture.display(),
+                        source
+                    ))
+                })?;
+                material.map_kd = Some(image);
+            }
+            _ => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| parser.error("missing illumination model"))?;
+                material.illum = token
+                    .parse()
+                    .map_err(|_| parser.error(format!("invalid illumination model `{}`", token)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// position, texture coordinate and normal indices of one face corner
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+// faces are collected per group and material, each run becomes its own mesh
+struct Run {
+    material: MaterialKind,
+    faces: Vec<[FaceVertex; 3]>,
+}
+
+pub fn load_obj(path: &Path, default_material: MaterialKind) -> Result<HittableList, ObjError> {
+    parse_obj(path, &read(path)?, default_material)
+}
+
+// `path` locates errors and the material libraries the source refers to
+fn parse_obj(
+    path: &Path,
+    source: &str,
+    default_material: MaterialKind,
+) -> Result<HittableList, ObjError> {
+    let mut parser = Parser { path, line: 0 };
+
+    let mut positions: Vec<Point> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<(f64, f64)> = vec![];
+    let mut materials: HashMap<String, MtlMaterial> = HashMap::new();
+
+    let mut runs = vec![Run {
+        material: default_material,
+        faces: vec![],
+    }];
+
+    for (n, line) in source.lines().enumerate() {
+        parser.line = n + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => positions.push(parser.vec3(&mut tokens)?),
+            "vn" => {
+                let normal = parser.vec3(&mut tokens)?;
+                if normal.near_zero() || !normal.length().is_finite() {
+                    return Err(parser.error("normal must be finite and non-zero"));
+                }
+                normals.push(normal.normalized());
+            }
+            "vt" => {
+                let u = parser.f64(tokens.next(), "u coordinate")?;
+                let v = match tokens.next() {
+                    Some(token) => parser.f64(Some(token), "v coordinate")?,
+                    None => 0.,
+                };
+                uvs.push((u, v));
+            }
+            "f" => {
+                let mut vertices = vec![];
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let position = parts.next().unwrap_or_default();
+                    let position = parser.index(position, positions.len(), "position")?;
+                    let uv = match parts.next() {
+                        Some("") | None => None,
+                        Some(t) => Some(parser.index(t, uvs.len(), "texture coordinate")?),
+                    };
+                    let normal = match parts.next() {
+                        Some("") | None => None,
+                        Some(t) => Some(parser.index(t, normals.len(), "normal")?),
+                    };
+                    if parts.next().is_some() {
+                        return Err(parser.error(format!("invalid face vertex `{}`", token)));
+                    }
+                    vertices.push((position, uv, normal));
+                }
+                if vertices.len() < 3 {
+                    return Err(parser.error("face needs at least three vertices"));
+                }
+
+                let run = runs.last_mut().unwrap();
+                for i in 1..vertices.len() - 1 {
+                    run.faces.push([vertices[0], vertices[i], vertices[i + 1]]);
+                }
+            }
+            "g" | "o" => {
+                let material = runs.last().unwrap().material.clone();
+                runs.push(Run {
+                    material,
+                    faces: vec![],
+                });
+            }
+            "usemtl" => {
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| parser.error("missing material name"))?;
+                let material = materials
+                    .get(name)
+                    .ok_or_else(|| parser.error(format!("unknown material `{}`", name)))?
+                    .to_material();
+                runs.push(Run {
+                    material,
+                    faces: vec![],
+                });
+            }
+            "mtllib" => {
+                let dir = path.parent().unwrap_or(Path::new(""));
+                let mut any = false;
+                for file in tokens {
+                    // errors inside the library point to its own lines
+                    load_mtl(&dir.join(file), &mut materials).map_err(|err| match err {
+                        ObjError::Io { path, source } => parser.error(format!(
+                            "cannot read material library {}: {}",
+                            path.display(),
+                            source
+                        )),
+                        err => err,
+                    })?;
+                    any = true;
+                }
+                if !any {
+                    return Err(parser.error("missing material library file name"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let objects = runs
+        .into_iter()
+        .filter(|run| !run.faces.is_empty())
+        .map(|run| {
+            HittableKind::TriangleMesh(TriangleMesh::new(build_mesh(
+                run, &positions, &normals, &uvs,
+            )))
+        })
+        .collect();
+
+    Ok(HittableList { objects })
+}
+
+// copies only the vertices a run references into its own compact buffers
+fn build_mesh(run: Run, positions: &[Point], normals: &[Vec3], uvs: &[(f64, f64)]) -> Mesh {
+    let mut mesh = Mesh {
+        positions: vec![],
+        normals: vec![],
+        uvs: vec![],
+        faces: Vec::with_capacity(run.faces.len()),
+        material: run.material,
+    };
+    let mut position_map = HashMap::new();
+    let mut normal_map = HashMap::new();
+    let mut uv_map = HashMap::new();
+
+    fn remap<T: Copy>(
+        i: usize,
+        src: &[T],
+        dst: &mut Vec<T>,
+        map: &mut HashMap<usize, usize>,
+    ) -> usize {
+        *map.entry(i).or_insert_with(|| {
+            dst.push(src[i]);
+            dst.len() - 1
+        })
+    }
+
+    for face in run.faces {
+        let face_positions =
+            face.map(|(p, _, _)| remap(p, positions, &mut mesh.positions, &mut position_map));
+        let face_uvs = face
+            .iter()
+            .all(|(_, uv, _)| uv.is_some())
+            .then(|| face.map(|(_, uv, _)| remap(uv.unwrap(), uvs, &mut mesh.uvs, &mut uv_map)));
+        let face_normals = face.iter().all(|(_, _, n)| n.is_some()).then(|| {
+            face.map(|(_, _, n)| remap(n.unwrap(), normals, &mut mesh.normals, &mut normal_map))
+        });
+
+        mesh.faces.push(MeshFace {
+            positions: face_positions,
+            normals: face_normals,
+            uvs: face_uvs,
+        });
+    }
+
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<HittableList, ObjError> {
+        let material = MaterialKind::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5).into(),
+        });
+        parse_obj(Path::new("test.obj"), source, material)
+    }
+
+    fn error_line(source: &str) -> (usize, String) {
+        match parse(source) {
+            Err(ObjError::Syntax { line, message, .. }) => (line, message),
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("parsed without an error"),
+        }
+    }
+
+    #[test]
+    fn groups_become_separate_meshes() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nf 1 2 3\ng top\nf 2 4 3\n";
+        assert_eq!(parse(source).unwrap().objects.len(), 2);
+    }
+
+    #[test]
+    fn errors_point_to_their_line() {
+        let (line, message) = error_line("v 0 0 0\n\nv 1 x 0\n");
+        assert_eq!(line, 3);
+        assert_eq!(message, "invalid y coordinate `x`");
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        let (line, message) = error_line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n");
+        assert_eq!(line, 4);
+        assert_eq!(message, "position index 4 out of range");
+    }
+
+    #[test]
+    fn negative_indices_count_back() {
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n").is_ok());
+        let (line, _) = error_line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 -2 -1\n");
+        assert_eq!(line, 4);
+    }
+
+    #[test]
+    fn zero_normals_are_rejected() {
+        let (line, message) = error_line("v 0 0 0\nvn 0 0 0\n");
+        assert_eq!(line, 2);
+        assert_eq!(message, "normal must be finite and non-zero");
+    }
+
+    #[test]
+    fn faces_need_three_vertices() {
+        let (line, message) = error_line("v 0 0 0\nv 1 0 0\nf 1 2\n");
+        assert_eq!(line, 3);
+        assert_eq!(message, "face needs at least three vertices");
+    }
+
+    #[test]
+    fn unknown_materials_are_rejected() {
+        let (line, message) = error_line("# no library\nusemtl brass\n");
+        assert_eq!(line, 2);
+        assert_eq!(message, "unknown material `brass`");
+    }
+}
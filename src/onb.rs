@@ -0,0 +1,49 @@
+use crate::vec3::Vec3;
+
+// orthonormal basis around `w`, used to place directions sampled about the
+// z axis around a normal or another direction
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn new(n: Vec3) -> Onb {
+        let w = n.normalized();
+        let helper = if w.x().abs() > 0.9 {
+            Vec3::new(0., 1., 0.)
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let v = w.cross(helper).normalized();
+        let u = w.cross(v);
+        Onb { u, v, w }
+    }
+
+    // tangents picked so that a floor gets u along +x and a wall facing +z
+    // gets u along +x and v along +y, for texture coordinates and the
+    // orientation of anisotropic materials
+    pub fn from_normal(n: Vec3) -> Onb {
+        let helper = if n.y().abs() < 0.999 {
+            Vec3::new(0., 1., 0.)
+        } else {
+            Vec3::new(0., 0., -n.y().signum())
+        };
+        let u = helper.cross(n).normalized();
+        Onb {
+            u,
+            v: n.cross(u),
+            w: n,
+        }
+    }
+
+    pub fn transform(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use image::{
+    codecs::{hdr::HdrEncoder, openexr::OpenExrEncoder, png::PngEncoder},
+    ExtendedColorType, ImageEncoder,
+};
+
+use crate::vec3::Color;
+
+// linear radiance, one entry per pixel in scanline order
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| pixel.to_rgb().map(|c| c as u8))
+            .collect()
+    }
+
+    // unclamped linear radiance as native-endian f32 triplets
+    fn to_rgb32f(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| [pixel.x(), pixel.y(), pixel.z()])
+            .flat_map(|c| (c as f32).to_ne_bytes())
+            .collect()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum)]
+pub enum ImageFormat {
+    Png,
+    /// Binary PPM
+    P6,
+    /// ASCII PPM
+    P3,
+    /// 32-bit float OpenEXR, linear
+    Exr,
+    /// Radiance RGBE, linear
+    Hdr,
+    /// Portable float map, linear
+    Pfm,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::P6),
+            "exr" => Some(ImageFormat::Exr),
+            "hdr" => Some(ImageFormat::Hdr),
+            "pfm" => Some(ImageFormat::Pfm),
+            _ => None,
+        }
+    }
+
+    pub fn write(self, file: File, image: &Image) -> io::Result<()> {
+        let mut out = BufWriter::new(file);
+        match self {
+            ImageFormat::Png => PngEncoder::new(&mut out)
+                .write_image(
+                    &image.to_rgb8(),
+                    image.width as u32,
+                    image.height as u32,
+                    ExtendedColorType::Rgb8,
+                )
+                .map_err(io::Error::other)?,
+            ImageFormat::P6 => {
+                write!(out, "P6\n{} {}\n255\n", image.width, image.height)?;
+                out.write_all(&image.to_rgb8())?;
+            }
+            ImageFormat::P3 => {
+                write!(out, "P3\n{} {}\n255\n", image.width, image.height)?;
+                for pixel in &image.pixels {
+                    write!(out, "{}", pixel.format())?;
+                }
+            }
+            ImageFormat::Exr => OpenExrEncoder::new(&mut out)
+                .write_image(
+                    &image.to_rgb32f(),
+                    image.width as u32,
+                    image.height as u32,
+                    ExtendedColorType::Rgb32F,
+                )
+                .map_err(io::Error::other)?,
+            ImageFormat::Hdr => HdrEncoder::new(&mut out)
+                .write_image(
+                    &image.to_rgb32f(),
+                    image.width as u32,
+                    image.height as u32,
+                    ExtendedColorType::Rgb32F,
+                )
+                .map_err(io::Error::other)?,
+            ImageFormat::Pfm => {
+                // a negative scale marks little-endian data, rows go bottom to top
+                write!(out, "PF\n{} {}\n-1.0\n", image.width, image.height)?;
+                for row in image.pixels.chunks(image.width).rev() {
+                    for pixel in row {
+                        for c in [pixel.x(), pixel.y(), pixel.z()] {
+                            out.write_all(&(c as f32).to_le_bytes())?;
+                        }
+                    }
+                }
+            }
+        }
+        out.flush()
+    }
+}
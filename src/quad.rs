@@ -0,0 +1,275 @@
+use std::f64::consts::PI;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::MaterialKind,
+    onb::Onb,
+    random::random_f64,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+// parallelogram spanned by the edges `u` and `v` from the corner `origin`, the
+// front side faces u x v and texture coordinates run along the two edges
+#[derive(Clone)]
+pub struct Quad {
+    origin: Point,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    normal: Vec3,
+    d: f64,
+    area: f64,
+    pub material: MaterialKind,
+    bbox: Aabb,
+}
+
+impl Quad {
+    pub fn new(origin: Point, u: Vec3, v: Vec3, material: MaterialKind) -> Quad {
+        let n = u.cross(v);
+        let normal = n.normalized();
+        let bbox = Aabb::surrounding(
+            Aabb::from_points(origin, origin + u + v),
+            Aabb::from_points(origin + u, origin + v),
+        );
+
+        Quad {
+            origin,
+            u,
+            v,
+            w: n / n.length_squared(),
+            normal,
+            d: normal.dot(origin),
+            area: n.length(),
+            material,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let t = plane_hit(self.normal, self.d, ray, ray_t)?;
+        let point = ray.at(t);
+
+        // coordinates of the hit point in the frame of the two edges
+        let planar = point - self.origin;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0. ..=1.).contains(&alpha) || !(0. ..=1.).contains(&beta) {
+            return None;
+        }
+
+        Some(
+            HitRecord::new(ray, &point, t, self.normal, (alpha, beta), &self.material)
+                .with_tangents(self.u, self.v),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // points are picked uniformly by area, which is dist^2 / (cos * area) as
+    // a density over directions
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        let Some(hit_rec) = self.hit(ray, Interval::new(0.001, f64::INFINITY)) else {
+            return 0.;
+        };
+        let distance_squared = hit_rec.t * hit_rec.t * ray.dir.length_squared();
+        let cosine = ray.dir.dot(self.normal).abs() / ray.dir.length();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Point, _: f64) -> Vec3 {
+        self.origin + random_f64() * self.u + random_f64() * self.v - origin
+    }
+}
+
+// infinite plane through `point`, texture coordinates are distances along two
+// directions in the plane and so repeat once per unit
+pub struct Plane {
+    point: Point,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    d: f64,
+    material: MaterialKind,
+    bbox: Aabb,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vec3, material: MaterialKind) -> Plane {
+        let normal = normal.normalized();
+        let frame = Onb::from_normal(normal);
+
+        // only planes facing along an axis are bounded in any direction
+        let axis = (0..3).find(|&axis| normal[axis].abs() == 1.);
+        let slab = |n| match axis {
+            Some(axis) if axis == n => Interval::new(point[n], point[n]),
+            _ => Interval::UNIVERSE,
+        };
+        let bbox = Aabb::new(slab(0), slab(1), slab(2));
+
+        Plane {
+            point,
+            normal,
+            tangent: frame.u,
+            bitangent: frame.v,
+            d: normal.dot(point),
+            material,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let t = plane_hit(self.normal, self.d, ray, ray_t)?;
+        let point = ray.at(t);
+        let planar = point - self.point;
+
+        Some(HitRecord::new(
+            ray,
+            &point,
+            t,
+            self.normal,
+            (self.tangent.dot(planar), self.bitangent.dot(planar)),
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// u runs once around the center and v from the center out to the rim
+pub struct Disk {
+    center: Point,
+    normal: Vec3,
+    radius: f64,
+    tangent: Vec3,
+    bitangent: Vec3,
+    d: f64,
+    material: MaterialKind,
+    bbox: Aabb,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vec3, radius: f64, material: MaterialKind) -> Disk {
+        let normal = normal.normalized();
+        let frame = Onb::from_normal(normal);
+
+        // the rim reaches furthest along the axes the disk is not facing
+        let extent = |n: f64| radius * (1. - n * n).max(0.).sqrt();
+        let extent = Vec3::new(extent(normal.x()), extent(normal.y()), extent(normal.z()));
+
+        Disk {
+            center,
+            normal,
+            radius,
+            tangent: frame.u,
+            bitangent: frame.v,
+            d: normal.dot(center),
+            material,
+            bbox: Aabb::from_points(center - extent, center + extent),
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let t = plane_hit(self.normal, self.d, ray, ray_t)?;
+        let point = ray.at(t);
+        let planar = point - self.center;
+        if planar.length_squared() > self.radius * self.radius {
+            return None;
+        }
+
+        let phi = self.bitangent.dot(planar).atan2(self.tangent.dot(planar));
+        let u = (phi + PI) / (2. * PI);
+        let v = planar.length() / self.radius;
+
+        // u turns around the normal, v points away from the center
+        let around = self.normal.cross(planar);
+        let hit_rec = HitRecord::new(ray, &point, t, self.normal, (u, v), &self.material);
+        Some(if v > 0. {
+            hit_rec.with_tangents(2. * PI * around, planar / v)
+        } else {
+            hit_rec
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// axis-aligned box between two opposite corners, made of six outward facing
+// quads
+pub struct Cuboid {
+    sides: Box<[Quad; 6]>,
+    bbox: Aabb,
+}
+
+impl Cuboid {
+    pub fn new(a: Point, b: Point, material: MaterialKind) -> Cuboid {
+        let min = Point::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+        let max = Point::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+        let dx = Vec3::new(max.x() - min.x(), 0., 0.);
+        let dy = Vec3::new(0., max.y() - min.y(), 0.);
+        let dz = Vec3::new(0., 0., max.z() - min.z());
+
+        let side = |origin, u, v| Quad::new(origin, u, v, material.clone());
+        let sides = [
+            side(Point::new(min.x(), min.y(), max.z()), dx, dy), // front
+            side(Point::new(max.x(), min.y(), max.z()), -dz, dy), // right
+            side(Point::new(max.x(), min.y(), min.z()), -dx, dy), // back
+            side(Point::new(min.x(), min.y(), min.z()), dz, dy), // left
+            side(Point::new(min.x(), max.y(), max.z()), dx, -dz), // top
+            side(Point::new(min.x(), min.y(), min.z()), dx, dz), // bottom
+        ];
+
+        Cuboid {
+            sides: Box::new(sides),
+            bbox: Aabb::from_points(min, max),
+        }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(ray, ray_t) {
+            return None;
+        }
+
+        let mut current_hit = None;
+        let mut interval = ray_t;
+        for side in self.sides.iter() {
+            if let Some(hit_rec) = side.hit(ray, interval) {
+                interval.max = hit_rec.t;
+                current_hit = Some(hit_rec);
+            }
+        }
+        current_hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// ray parameter where the ray crosses the plane n . p = d, if it is in range
+fn plane_hit(normal: Vec3, d: f64, ray: &Ray, ray_t: Interval) -> Option<f64> {
+    let denom = normal.dot(ray.dir);
+    if denom.abs() < 1e-8 {
+        return None;
+    }
+
+    let t = (d - normal.dot(ray.origin)) / denom;
+    ray_t.surrounds(t).then_some(t)
+}
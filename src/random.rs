@@ -5,3 +5,7 @@ pub fn random_f64() -> f64 {
 pub fn random_f64_between(min: f64, max: f64) -> f64 {
     min + (max - min) * fastrand::f64()
 }
+
+pub fn random_index(len: usize) -> usize {
+    fastrand::usize(..len)
+}
@@ -4,14 +4,22 @@ use crate::vec3::{Point, Vec3};
 pub struct Ray {
     pub origin: Point,
     pub dir: Vec3,
+    pub time: f64,
+    // the wavelength in nanometers a path is traced at in spectral mode
+    pub wavelength: Option<f64>,
 }
 
 impl Ray {
-    pub fn new(origin: Point, dir: Vec3) -> Self {
-        return Ray { origin, dir };
+    pub fn new(origin: Point, dir: Vec3, time: f64) -> Self {
+        Ray {
+            origin,
+            dir,
+            time,
+            wavelength: None,
+        }
     }
 
     pub fn at(self, t: f64) -> Point {
-        return self.origin + t * self.dir;
+        self.origin + t * self.dir
     }
 }
@@ -0,0 +1,1658 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use crate::{
+    aabb::Aabb,
+    bvh::{BvhNode, SplitStrategy},
+    camera::Camera,
+    environment::{Environment, EnvironmentMap},
+    hittable::{Hittable, HittableKind, HittableList},
+    instance::Instance,
+    light::{DirectionalLight, LightKind, PointLight, SpotLight},
+    material::{
+        Bump, Bumped, Conductor, ConductorPreset, Dielectric, DiffuseLight, Dispersion, Isotropic,
+        Lambertian, MaterialKind, Metal, Principled,
+    },
+    medium::{ConstantMedium, DensityField, Medium, Volume, VoxelGrid},
+    microfacet::Ggx,
+    noise::{Fractal, Perlin},
+    obj::load_obj,
+    quad::{Cuboid, Disk, Plane, Quad},
+    spectrum,
+    sphere::Sphere,
+    texture::{
+        Checker, ColorSpace, Filter, ImageTexture, Marble, Noise, TextureKind, UvChecker, Wood,
+        Worley, Wrap,
+    },
+    transform::{AnimatedTransform, Transform},
+    triangle::Triangle,
+    vec3::{Color, Point, Vec3},
+};
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        key: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Parse { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Invalid { path, key, message } => {
+                write!(f, "{}: `{}`: {}", path.display(), key, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Parse { source, .. } => Some(source),
+            SceneError::Invalid { .. } => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraSettings {
+    pub aspect_ratio: f64,
+    pub image_width: i32,
+    // derived from the aspect ratio when omitted
+    #[serde(default)]
+    pub image_height: Option<i32>,
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,
+    pub vfov: f64,
+    pub look_from: [f64; 3],
+    pub look_at: [f64; 3],
+    #[serde(default = "default_vup")]
+    pub vup: [f64; 3],
+    #[serde(default)]
+    pub defocus_angle: f64,
+    #[serde(default = "default_focus_dist")]
+    pub focus_dist: f64,
+    #[serde(default)]
+    pub shutter_open: f64,
+    #[serde(default = "default_shutter_close")]
+    pub shutter_close: f64,
+    #[serde(default)]
+    pub spectral: bool,
+}
+
+fn default_vup() -> [f64; 3] {
+    [0., 1., 0.]
+}
+
+fn default_focus_dist() -> f64 {
+    10.
+}
+
+fn default_shutter_close() -> f64 {
+    1.
+}
+
+impl CameraSettings {
+    // returns the offending key along with the reason
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        let positive = |key, value: f64| {
+            if value > 0. && value.is_finite() {
+                Ok(())
+            } else {
+                Err((key, format!("must be positive, got {}", value)))
+            }
+        };
+        positive("aspect_ratio", self.aspect_ratio)?;
+        positive("focus_dist", self.focus_dist)?;
+        if self.image_width <= 0 {
+            return Err((
+                "image_width",
+                format!("must be positive, got {}", self.image_width),
+            ));
+        }
+        if let Some(height) = self.image_height.filter(|&height| height <= 0) {
+            return Err(("image_height", format!("must be positive, got {}", height)));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err(("samples_per_pixel", "must be at least 1".to_owned()));
+        }
+        if self.max_depth == 0 {
+            return Err(("max_depth", "must be at least 1".to_owned()));
+        }
+        if !(self.vfov > 0. && self.vfov < 180.) {
+            return Err((
+                "vfov",
+                format!("must be between 0 and 180 degrees, got {}", self.vfov),
+            ));
+        }
+        if !(0. ..180.).contains(&self.defocus_angle) {
+            return Err((
+                "defocus_angle",
+                format!(
+                    "must be between 0 and 180 degrees, got {}",
+                    self.defocus_angle
+                ),
+            ));
+        }
+        if self.shutter_close < self.shutter_open {
+            return Err((
+                "shutter_close",
+                format!(
+                    "must not be before shutter_open, got {}",
+                    self.shutter_close
+                ),
+            ));
+        }
+        if self.look_from == self.look_at {
+            return Err(("look_at", "must differ from look_from".to_owned()));
+        }
+        let view = Vec3::from(self.look_from) - Vec3::from(self.look_at);
+        if view.cross(Vec3::from(self.vup)).length_squared() < 1e-12 {
+            return Err((
+                "vup",
+                "must not be parallel to the view direction".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn build(&self) -> Camera {
+        Camera::init(
+            self.aspect_ratio,
+            self.image_width,
+            self.image_height,
+            self.samples_per_pixel,
+            self.max_depth,
+            self.vfov,
+            self.look_from.into(),
+            self.look_at.into(),
+            self.vup.into(),
+            self.defocus_angle,
+            self.focus_dist,
+            self.shutter_open,
+            self.shutter_close,
+            self.spectral,
+        )
+    }
+}
+
+// enums are externally tagged, internally tagged ones lose the source span
+// of the offending key in toml's error messages
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid {
+        color: [f64; 3],
+    },
+    Checker {
+        scale: f64,
+        even: TextureRef,
+        odd: TextureRef,
+    },
+    UvChecker {
+        columns: f64,
+        rows: f64,
+        even: TextureRef,
+        odd: TextureRef,
+    },
+    Image {
+        path: PathBuf,
+        color_space: Option<ColorSpace>,
+        #[serde(default)]
+        filter: Filter,
+        #[serde(default)]
+        wrap: Wrap,
+        wrap_u: Option<Wrap>,
+        wrap_v: Option<Wrap>,
+    },
+    Noise {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default)]
+        fractal: Fractal,
+        #[serde(default)]
+        turbulence: bool,
+        #[serde(default = "default_low")]
+        low: TextureRef,
+        #[serde(default = "default_high")]
+        high: TextureRef,
+    },
+    Marble {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default)]
+        fractal: Fractal,
+        #[serde(default = "default_marble_strength")]
+        strength: f64,
+        #[serde(default = "default_low")]
+        low: TextureRef,
+        #[serde(default = "default_high")]
+        high: TextureRef,
+    },
+    Wood {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default)]
+        fractal: Fractal,
+        #[serde(default = "default_wood_strength")]
+        strength: f64,
+        #[serde(default = "default_low")]
+        low: TextureRef,
+        #[serde(default = "default_high")]
+        high: TextureRef,
+    },
+    Worley {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default = "default_low")]
+        low: TextureRef,
+        #[serde(default = "default_high")]
+        high: TextureRef,
+    },
+}
+
+fn default_transmittance_distance() -> f64 {
+    1.
+}
+
+fn default_base_color() -> TextureRef {
+    TextureRef::Color([0.8, 0.8, 0.8])
+}
+
+fn default_half() -> f64 {
+    0.5
+}
+
+fn default_ior() -> f64 {
+    1.5
+}
+
+fn default_one() -> f64 {
+    1.
+}
+
+fn default_scale() -> f64 {
+    1.
+}
+
+fn default_marble_strength() -> f64 {
+    10.
+}
+
+fn default_wood_strength() -> f64 {
+    0.2
+}
+
+fn default_low() -> TextureRef {
+    TextureRef::Color([0., 0., 0.])
+}
+
+fn default_high() -> TextureRef {
+    TextureRef::Color([1., 1., 1.])
+}
+
+// a texture is a plain color, the name of an entry of `[textures]` or an
+// inline texture
+#[derive(Clone, Debug)]
+enum TextureRef {
+    Color([f64; 3]),
+    Named(String),
+    Inline(Box<TextureDesc>),
+}
+
+impl<'de> Deserialize<'de> for TextureRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TextureRefVisitor;
+
+        impl<'de> Visitor<'de> for TextureRefVisitor {
+            type Value = TextureRef;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a color, a texture name or an inline texture")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<TextureRef, A::Error> {
+                <[f64; 3]>::deserialize(de::value::SeqAccessDeserializer::new(seq))
+                    .map(TextureRef::Color)
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<TextureRef, E> {
+                Ok(TextureRef::Named(name.to_owned()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<TextureRef, A::Error> {
+                TextureDesc::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(|desc| TextureRef::Inline(Box::new(desc)))
+            }
+        }
+
+        deserializer.deserialize_any(TextureRefVisitor)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian {
+        albedo: TextureRef,
+    },
+    Metal {
+        albedo: TextureRef,
+        fuzz: f64,
+    },
+    // either a preset or eta and k
+    Conductor {
+        preset: Option<ConductorPreset>,
+        eta: Option<[f64; 3]>,
+        k: Option<[f64; 3]>,
+        #[serde(default)]
+        roughness: f64,
+        #[serde(default)]
+        anisotropy: f64,
+    },
+    // the refraction index defaults to the one of the dispersion at the d line
+    Dielectric {
+        refraction_index: Option<f64>,
+        dispersion: Option<Dispersion>,
+        #[serde(default)]
+        roughness: f64,
+        // the color left after light travelled `transmittance_distance`
+        // through the inside
+        transmittance: Option<[f64; 3]>,
+        #[serde(default = "default_transmittance_distance")]
+        transmittance_distance: f64,
+    },
+    // all but the base color default to a white dielectric plastic
+    Principled {
+        #[serde(default = "default_base_color")]
+        base_color: TextureRef,
+        #[serde(default)]
+        metallic: f64,
+        #[serde(default = "default_half")]
+        roughness: f64,
+        #[serde(default)]
+        anisotropy: f64,
+        #[serde(default = "default_half")]
+        specular: f64,
+        #[serde(default)]
+        specular_tint: f64,
+        #[serde(default)]
+        transmission: f64,
+        #[serde(default = "default_ior")]
+        ior: f64,
+        #[serde(default)]
+        clearcoat: f64,
+        #[serde(default = "default_one")]
+        clearcoat_gloss: f64,
+        #[serde(default)]
+        sheen: f64,
+        #[serde(default = "default_half")]
+        sheen_tint: f64,
+        transmittance: Option<[f64; 3]>,
+        #[serde(default = "default_transmittance_distance")]
+        transmittance_distance: f64,
+    },
+    // wrap another material, see `Bump`
+    NormalMap {
+        material: Box<MaterialRef>,
+        texture: TextureRef,
+        #[serde(default = "default_one")]
+        strength: f64,
+    },
+    BumpMap {
+        material: Box<MaterialRef>,
+        texture: TextureRef,
+        scale: f64,
+    },
+    DiffuseLight {
+        emit: TextureRef,
+        #[serde(default)]
+        two_sided: bool,
+    },
+    Isotropic {
+        albedo: TextureRef,
+    },
+}
+
+// objects either name an entry of `[materials]` or describe one inline
+#[derive(Clone, Debug)]
+enum MaterialRef {
+    Named(String),
+    Inline(MaterialDesc),
+}
+
+impl<'de> Deserialize<'de> for MaterialRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MaterialRefVisitor;
+
+        impl<'de> Visitor<'de> for MaterialRefVisitor {
+            type Value = MaterialRef;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a material name or an inline material")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<MaterialRef, E> {
+                Ok(MaterialRef::Named(name.to_owned()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<MaterialRef, A::Error> {
+                MaterialDesc::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(MaterialRef::Inline)
+            }
+        }
+
+        deserializer.deserialize_any(MaterialRefVisitor)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        center: [f64; 3],
+        center1: Option<[f64; 3]>,
+        // when the sphere is at `center` and at `center1`
+        #[serde(default)]
+        time0: f64,
+        #[serde(default = "default_one")]
+        time1: f64,
+        radius: f64,
+        material: MaterialRef,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        material: MaterialRef,
+    },
+    Mesh {
+        path: PathBuf,
+        material: Option<MaterialRef>,
+    },
+    Quad {
+        origin: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: MaterialRef,
+    },
+    Plane {
+        point: [f64; 3],
+        normal: [f64; 3],
+        material: MaterialRef,
+    },
+    Disk {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        material: MaterialRef,
+    },
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: MaterialRef,
+    },
+    Instance {
+        shape: ShapeRef,
+        #[serde(default)]
+        transform: Vec<TransformDesc>,
+        #[serde(default)]
+        keyframes: Vec<KeyframeDesc>,
+    },
+    ConstantMedium {
+        boundary: ShapeRef,
+        density: f64,
+        material: MaterialRef,
+    },
+    Volume {
+        boundary: ShapeRef,
+        medium: MediumDesc,
+        material: Option<MaterialRef>,
+    },
+    Group {
+        objects: Vec<ObjectDesc>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MediumDesc {
+    density: f64,
+    #[serde(default = "default_albedo")]
+    albedo: [f64; 3],
+    #[serde(default)]
+    g: f64,
+    #[serde(default)]
+    field: DensityFieldDesc,
+}
+
+fn default_albedo() -> [f64; 3] {
+    [1., 1., 1.]
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum DensityFieldDesc {
+    #[default]
+    Uniform,
+    // the grid spans the bounding box of the boundary unless given
+    Grid {
+        path: PathBuf,
+        resolution: [usize; 3],
+        min: Option<[f64; 3]>,
+        max: Option<[f64; 3]>,
+    },
+    Noise {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default)]
+        fractal: Fractal,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDesc {
+    time: f64,
+    transform: Vec<TransformDesc>,
+}
+
+// instances either name an entry of `[shapes]`, which is built once and shared,
+// or describe their object inline
+#[derive(Clone, Debug)]
+enum ShapeRef {
+    Named(String),
+    Inline(Box<ObjectDesc>),
+}
+
+impl<'de> Deserialize<'de> for ShapeRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ShapeRefVisitor;
+
+        impl<'de> Visitor<'de> for ShapeRefVisitor {
+            type Value = ShapeRef;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a shape name or an inline object")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<ShapeRef, E> {
+                Ok(ShapeRef::Named(name.to_owned()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<ShapeRef, A::Error> {
+                ObjectDesc::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(|desc| ShapeRef::Inline(Box::new(desc)))
+            }
+        }
+
+        deserializer.deserialize_any(ShapeRefVisitor)
+    }
+}
+
+// steps are applied in the order they are listed
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformDesc {
+    Translate([f64; 3]),
+    Scale([f64; 3]),
+    Rotate { axis: [f64; 3], angle: f64 },
+    Matrix([[f64; 4]; 4]),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum EnvironmentDesc {
+    Solid {
+        color: [f64; 3],
+    },
+    Gradient {
+        bottom: [f64; 3],
+        top: [f64; 3],
+    },
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+}
+
+fn default_intensity() -> f64 {
+    1.
+}
+
+// angles are in degrees, spot light angles are measured from the axis
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum LightDesc {
+    Point {
+        position: [f64; 3],
+        intensity: [f64; 3],
+    },
+    Spot {
+        position: [f64; 3],
+        direction: [f64; 3],
+        intensity: [f64; 3],
+        inner_angle: Option<f64>,
+        outer_angle: f64,
+    },
+    Directional {
+        direction: [f64; 3],
+        intensity: [f64; 3],
+        #[serde(default)]
+        angular_diameter: f64,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    camera: CameraSettings,
+    environment: Option<EnvironmentDesc>,
+    #[serde(default)]
+    textures: HashMap<String, TextureDesc>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDesc>,
+    #[serde(default)]
+    shapes: HashMap<String, ObjectDesc>,
+    #[serde(default)]
+    objects: Vec<ObjectDesc>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+}
+
+// named textures and shapes may refer to each other, deeper chains are taken
+// as a cycle
+const MAX_TEXTURE_DEPTH: usize = 32;
+const MAX_SHAPE_DEPTH: usize = 32;
+const MAX_MATERIAL_DEPTH: usize = 32;
+
+// resolves references and relative paths against the scene file, keys in
+// errors are spelled the way they appear in the file
+struct Loader<'a> {
+    path: &'a Path,
+    textures: &'a HashMap<String, TextureDesc>,
+    materials: &'a HashMap<String, MaterialDesc>,
+    shapes: &'a HashMap<String, ObjectDesc>,
+    built_shapes: RefCell<HashMap<String, Arc<HittableKind>>>,
+    images: RefCell<HashMap<(PathBuf, Option<ColorSpace>), ImageTexture>>,
+}
+
+impl Loader<'_> {
+    fn invalid(&self, key: impl Into<String>, message: impl Into<String>) -> SceneError {
+        SceneError::Invalid {
+            path: self.path.to_owned(),
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+
+    fn resolve(&self, relative: &Path) -> PathBuf {
+        self.path.parent().unwrap_or(Path::new("")).join(relative)
+    }
+
+    fn image(
+        &self,
+        relative: &Path,
+        color_space: Option<ColorSpace>,
+        key: &str,
+    ) -> Result<ImageTexture, SceneError> {
+        let path = self.resolve(relative);
+        if let Some(image) = self.images.borrow().get(&(path.clone(), color_space)) {
+            return Ok(image.clone());
+        }
+
+        let image = ImageTexture::load(&path, color_space)
+            .map_err(|source| self.invalid(key, format!("{}: {}", path.display(), source)))?;
+        self.images
+            .borrow_mut()
+            .insert((path, color_space), image.clone());
+        Ok(image)
+    }
+
+    fn texture(
+        &self,
+        texture: &TextureRef,
+        key: &str,
+        depth: usize,
+    ) -> Result<TextureKind, SceneError> {
+        let desc = match texture {
+            TextureRef::Color(color) => return Ok(TextureKind::Solid((*color).into())),
+            TextureRef::Inline(desc) => desc,
+            TextureRef::Named(name) => {
+                if depth >= MAX_TEXTURE_DEPTH {
+                    return Err(self.invalid(key, format!("texture `{}` refers to itself", name)));
+                }
+                let desc = self
+                    .textures
+                    .get(name)
+                    .ok_or_else(|| self.invalid(key, format!("unknown texture `{}`", name)))?;
+                return self.texture_desc(desc, &format!("textures.{}", name), depth + 1);
+            }
+        };
+        self.texture_desc(desc, key, depth)
+    }
+
+    fn texture_desc(
+        &self,
+        desc: &TextureDesc,
+        key: &str,
+        depth: usize,
+    ) -> Result<TextureKind, SceneError> {
+        let field = |name: &str| format!("{}.{}", key, name);
+        Ok(match desc {
+            TextureDesc::Solid { color } => TextureKind::Solid((*color).into()),
+            TextureDesc::Checker { scale, even, odd } => {
+                if *scale <= 0. {
+                    return Err(self.invalid(field("scale"), "must be positive"));
+                }
+                TextureKind::Checker(Checker::new(
+                    *scale,
+                    self.texture(even, &field("even"), depth)?,
+                    self.texture(odd, &field("odd"), depth)?,
+                ))
+            }
+            TextureDesc::UvChecker {
+                columns,
+                rows,
+                even,
+                odd,
+            } => {
+                for (name, value) in [("columns", columns), ("rows", rows)] {
+                    if *value <= 0. || !value.is_finite() {
+                        return Err(self.invalid(field(name), "must be positive"));
+                    }
+                }
+                TextureKind::UvChecker(UvChecker::new(
+                    *columns,
+                    *rows,
+                    self.texture(even, &field("even"), depth)?,
+                    self.texture(odd, &field("odd"), depth)?,
+                ))
+            }
+            TextureDesc::Image {
+                path,
+                color_space,
+                filter,
+                wrap,
+                wrap_u,
+                wrap_v,
+            } => {
+                let mut image = self.image(path, *color_space, &field("path"))?;
+                image.filter = *filter;
+                image.wrap_u = wrap_u.unwrap_or(*wrap);
+                image.wrap_v = wrap_v.unwrap_or(*wrap);
+                TextureKind::Image(image)
+            }
+            TextureDesc::Noise {
+                seed,
+                scale,
+                fractal,
+                turbulence,
+                low,
+                high,
+            } => TextureKind::Noise(Noise::new(
+                *seed,
+                self.noise_scale(*scale, key)?,
+                self.fractal(fractal, key)?,
+                *turbulence,
+                self.texture(low, &field("low"), depth)?,
+                self.texture(high, &field("high"), depth)?,
+            )),
+            TextureDesc::Marble {
+                seed,
+                scale,
+                fractal,
+                strength,
+                low,
+                high,
+            } => TextureKind::Marble(Marble::new(
+                *seed,
+                self.noise_scale(*scale, key)?,
+                self.fractal(fractal, key)?,
+                *strength,
+                self.texture(low, &field("low"), depth)?,
+                self.texture(high, &field("high"), depth)?,
+            )),
+            TextureDesc::Wood {
+                seed,
+                scale,
+                fractal,
+                strength,
+                low,
+                high,
+            } => TextureKind::Wood(Wood::new(
+                *seed,
+                self.noise_scale(*scale, key)?,
+                self.fractal(fractal, key)?,
+                *strength,
+                self.texture(low, &field("low"), depth)?,
+                self.texture(high, &field("high"), depth)?,
+            )),
+            TextureDesc::Worley {
+                seed,
+                scale,
+                low,
+                high,
+            } => TextureKind::Worley(Worley::new(
+                *seed,
+                self.noise_scale(*scale, key)?,
+                self.texture(low, &field("low"), depth)?,
+                self.texture(high, &field("high"), depth)?,
+            )),
+        })
+    }
+
+    fn noise_scale(&self, scale: f64, key: &str) -> Result<f64, SceneError> {
+        if scale <= 0. {
+            return Err(self.invalid(format!("{}.scale", key), "must be positive"));
+        }
+        Ok(scale)
+    }
+
+    fn fractal(&self, fractal: &Fractal, key: &str) -> Result<Fractal, SceneError> {
+        let field = |name: &str| format!("{}.fractal.{}", key, name);
+        if fractal.octaves == 0 {
+            return Err(self.invalid(field("octaves"), "must be at least 1"));
+        }
+        if fractal.lacunarity <= 0. {
+            return Err(self.invalid(field("lacunarity"), "must be positive"));
+        }
+        Ok(*fractal)
+    }
+
+    fn material(
+        &self,
+        material: &MaterialRef,
+        key: &str,
+        depth: usize,
+    ) -> Result<MaterialKind, SceneError> {
+        match material {
+            MaterialRef::Named(name) => {
+                if depth >= MAX_MATERIAL_DEPTH {
+                    return Err(self.invalid(key, format!("material `{}` refers to itself", name)));
+                }
+                let desc = self
+                    .materials
+                    .get(name)
+                    .ok_or_else(|| self.invalid(key, format!("unknown material `{}`", name)))?;
+                self.material_desc(desc, &format!("materials.{}", name), depth + 1)
+            }
+            MaterialRef::Inline(desc) => self.material_desc(desc, key, depth),
+        }
+    }
+
+    fn material_desc(
+        &self,
+        desc: &MaterialDesc,
+        key: &str,
+        depth: usize,
+    ) -> Result<MaterialKind, SceneError> {
+        let field = |name: &str| format!("{}.{}", key, name);
+        Ok(match desc {
+            MaterialDesc::Lambertian { albedo } => MaterialKind::Lambertian(Lambertian {
+                albedo: self.texture(albedo, &field("albedo"), 0)?,
+            }),
+            MaterialDesc::Metal { albedo, fuzz } => {
+                if *fuzz < 0. || !fuzz.is_finite() {
+                    return Err(self.invalid(field("fuzz"), "must not be negative"));
+                }
+                MaterialKind::Metal(Metal {
+                    albedo: self.texture(albedo, &field("albedo"), 0)?,
+                    fuzz: *fuzz,
+                })
+            }
+            MaterialDesc::Conductor {
+                preset,
+                eta,
+                k,
+                roughness,
+                anisotropy,
+            } => {
+                let (eta, k) = match (preset, eta, k) {
+                    (Some(preset), None, None) => preset.ior(),
+                    (None, Some(eta), Some(k)) => ((*eta).into(), (*k).into()),
+                    _ => {
+                        return Err(self.invalid(key, "needs either a preset or both eta and k"));
+                    }
+                };
+                if !(0. ..=1.).contains(roughness) {
+                    return Err(self.invalid(field("roughness"), "must be between 0 and 1"));
+                }
+                if !(0. ..=1.).contains(anisotropy) {
+                    return Err(self.invalid(field("anisotropy"), "must be between 0 and 1"));
+                }
+                MaterialKind::Conductor(Conductor::new(eta, k, *roughness, *anisotropy))
+            }
+            MaterialDesc::Dielectric {
+                refraction_index,
+                dispersion,
+                roughness,
+                transmittance,
+                transmittance_distance,
+            } => {
+                if !(0. ..=1.).contains(roughness) {
+                    return Err(self.invalid(field("roughness"), "must be between 0 and 1"));
+                }
+                if let Some(dispersion) = dispersion {
+                    // every 10 nm across the visible range
+                    let valid = (38..=78).all(|step| {
+                        let n = dispersion.refraction_index(step as f64 * 10.);
+                        n > 0. && n.is_finite()
+                    });
+                    if !valid {
+                        return Err(self.invalid(
+                            field("dispersion"),
+                            "gives no valid refraction index over the visible range",
+                        ));
+                    }
+                }
+                let refraction_index = match (refraction_index, dispersion) {
+                    (Some(refraction_index), _) => *refraction_index,
+                    (None, Some(dispersion)) => dispersion.refraction_index(spectrum::D_LINE),
+                    (None, None) => {
+                        return Err(self.invalid(key, "needs a refraction_index or a dispersion"));
+                    }
+                };
+                MaterialKind::Dielectric(Dielectric {
+                    refraction_index,
+                    dispersion: *dispersion,
+                    distribution: (*roughness > 0.).then(|| Ggx::new(*roughness, 0.)),
+                    absorption: self.absorption(*transmittance, *transmittance_distance, key)?,
+                })
+            }
+            MaterialDesc::Principled {
+                base_color,
+                metallic,
+                roughness,
+                anisotropy,
+                specular,
+                specular_tint,
+                transmission,
+                ior,
+                clearcoat,
+                clearcoat_gloss,
+                sheen,
+                sheen_tint,
+                transmittance,
+                transmittance_distance,
+            } => {
+                let unit = [
+                    ("metallic", metallic),
+                    ("roughness", roughness),
+                    ("anisotropy", anisotropy),
+                    ("specular", specular),
+                    ("specular_tint", specular_tint),
+                    ("transmission", transmission),
+                    ("clearcoat", clearcoat),
+                    ("clearcoat_gloss", clearcoat_gloss),
+                    ("sheen", sheen),
+                    ("sheen_tint", sheen_tint),
+                ];
+                if let Some((name, _)) = unit.iter().find(|(_, v)| !(0. ..=1.).contains(*v)) {
+                    return Err(self.invalid(field(name), "must be between 0 and 1"));
+                }
+                if *ior < 1. {
+                    return Err(self.invalid(field("ior"), "must be at least 1"));
+                }
+                MaterialKind::Principled(Principled {
+                    base_color: self.texture(base_color, &field("base_color"), 0)?,
+                    metallic: *metallic,
+                    roughness: *roughness,
+                    anisotropy: *anisotropy,
+                    specular: *specular,
+                    specular_tint: *specular_tint,
+                    transmission: *transmission,
+                    ior: *ior,
+                    clearcoat: *clearcoat,
+                    clearcoat_gloss: *clearcoat_gloss,
+                    sheen: *sheen,
+                    sheen_tint: *sheen_tint,
+                    absorption: self.absorption(*transmittance, *transmittance_distance, key)?,
+                })
+            }
+            MaterialDesc::NormalMap {
+                material,
+                texture,
+                strength,
+            } => {
+                if *strength < 0. {
+                    return Err(self.invalid(field("strength"), "must not be negative"));
+                }
+                MaterialKind::Bumped(Box::new(Bumped {
+                    material: self.material(material, &field("material"), depth)?,
+                    bump: Bump::NormalMap {
+                        texture: self.texture(texture, &field("texture"), 0)?,
+                        strength: *strength,
+                    },
+                }))
+            }
+            MaterialDesc::BumpMap {
+                material,
+                texture,
+                scale,
+            } => MaterialKind::Bumped(Box::new(Bumped {
+                material: self.material(material, &field("material"), depth)?,
+                bump: Bump::HeightMap {
+                    texture: self.texture(texture, &field("texture"), 0)?,
+                    scale: *scale,
+                },
+            })),
+            MaterialDesc::DiffuseLight { emit, two_sided } => {
+                MaterialKind::DiffuseLight(DiffuseLight {
+                    emit: self.texture(emit, &field("emit"), 0)?,
+                    two_sided: *two_sided,
+                })
+            }
+            MaterialDesc::Isotropic { albedo } => MaterialKind::Isotropic(Isotropic {
+                albedo: self.texture(albedo, &field("albedo"), 0)?,
+            }),
+        })
+    }
+
+    // Beer-Lambert coefficients for the color left of white light after
+    // `distance` through the inside
+    fn absorption(
+        &self,
+        transmittance: Option<[f64; 3]>,
+        distance: f64,
+        key: &str,
+    ) -> Result<Color, SceneError> {
+        let field = |name: &str| format!("{}.{}", key, name);
+        if distance <= 0. {
+            return Err(self.invalid(field("transmittance_distance"), "must be positive"));
+        }
+        let transmittance = transmittance.unwrap_or([1., 1., 1.]);
+        if transmittance.iter().any(|c| !(*c > 0. && *c <= 1.)) {
+            return Err(self.invalid(
+                field("transmittance"),
+                "must be greater than 0 and at most 1",
+            ));
+        }
+        Ok(transmittance.map(|c| -c.ln() / distance).into())
+    }
+
+    fn object(
+        &self,
+        object: &ObjectDesc,
+        key: &str,
+        depth: usize,
+    ) -> Result<Vec<HittableKind>, SceneError> {
+        let field = |name: &str| format!("{}.{}", key, name);
+        Ok(match object {
+            ObjectDesc::Sphere {
+                center,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => {
+                if *radius <= 0. {
+                    return Err(self.invalid(field("radius"), "radius must be positive"));
+                }
+                if time1 <= time0 {
+                    return Err(self.invalid(field("time1"), "must be after time0"));
+                }
+                vec![HittableKind::Sphere(Sphere::moving(
+                    Point::from(*center),
+                    Point::from(center1.unwrap_or(*center)),
+                    *time0,
+                    *time1,
+                    *radius,
+                    self.material(material, &field("material"), 0)?,
+                ))]
+            }
+            ObjectDesc::Triangle {
+                vertices: [a, b, c],
+                material,
+            } => vec![HittableKind::Triangle(Triangle::new(
+                Point::from(*a),
+                Point::from(*b),
+                Point::from(*c),
+                self.material(material, &field("material"), 0)?,
+            ))],
+            ObjectDesc::Mesh { path, material } => {
+                let default_material = match material {
+                    Some(material) => self.material(material, &field("material"), 0)?,
+                    None => MaterialKind::Lambertian(Lambertian {
+                        albedo: Color::new(0.8, 0.8, 0.8).into(),
+                    }),
+                };
+                load_obj(&self.resolve(path), default_material)
+                    .map_err(|err| self.invalid(field("path"), err.to_string()))?
+                    .objects
+            }
+            ObjectDesc::Quad {
+                origin,
+                u,
+                v,
+                material,
+            } => {
+                let (u, v) = (Vec3::from(*u), Vec3::from(*v));
+                if u.cross(v).length_squared() == 0. {
+                    return Err(self.invalid(field("v"), "edges must not be parallel"));
+                }
+                vec![HittableKind::Quad(Quad::new(
+                    Point::from(*origin),
+                    u,
+                    v,
+                    self.material(material, &field("material"), 0)?,
+                ))]
+            }
+            ObjectDesc::Plane {
+                point,
+                normal,
+                material,
+            } => vec![HittableKind::Plane(Plane::new(
+                Point::from(*point),
+                self.normal(*normal, &field("normal"))?,
+                self.material(material, &field("material"), 0)?,
+            ))],
+            ObjectDesc::Disk {
+                center,
+                normal,
+                radius,
+                material,
+            } => {
+                if *radius <= 0. {
+                    return Err(self.invalid(field("radius"), "radius must be positive"));
+                }
+                vec![HittableKind::Disk(Disk::new(
+                    Point::from(*center),
+                    self.normal(*normal, &field("normal"))?,
+                    *radius,
+                    self.material(material, &field("material"), 0)?,
+                ))]
+            }
+            ObjectDesc::Box { min, max, material } => {
+                if (0..3).any(|axis| min[axis] >= max[axis]) {
+                    return Err(
+                        self.invalid(field("max"), "must be greater than min on every axis")
+                    );
+                }
+                vec![HittableKind::Box(Cuboid::new(
+                    Point::from(*min),
+                    Point::from(*max),
+                    self.material(material, &field("material"), 0)?,
+                ))]
+            }
+            ObjectDesc::Instance {
+                shape,
+                transform,
+                keyframes,
+            } => {
+                let transform = if keyframes.is_empty() {
+                    self.transform(transform, &field("transform"))?.into()
+                } else {
+                    if !transform.is_empty() {
+                        return Err(
+                            self.invalid(field("transform"), "cannot be combined with keyframes")
+                        );
+                    }
+                    let keys = keyframes
+                        .iter()
+                        .enumerate()
+                        .map(|(i, keyframe)| {
+                            let key = format!("{}[{}].transform", field("keyframes"), i);
+                            Ok((keyframe.time, self.transform(&keyframe.transform, &key)?))
+                        })
+                        .collect::<Result<_, SceneError>>()?;
+                    AnimatedTransform::new(keys)
+                };
+                vec![HittableKind::Instance(Instance::new(
+                    self.shape(shape, &field("shape"), depth)?,
+                    transform,
+                ))]
+            }
+            ObjectDesc::ConstantMedium {
+                boundary,
+                density,
+                material,
+            } => {
+                if *density <= 0. {
+                    return Err(self.invalid(field("density"), "must be positive"));
+                }
+                vec![HittableKind::ConstantMedium(ConstantMedium::new(
+                    self.shape(boundary, &field("boundary"), depth)?,
+                    *density,
+                    self.material(material, &field("material"), 0)?,
+                ))]
+            }
+            ObjectDesc::Volume {
+                boundary,
+                medium,
+                material,
+            } => {
+                let boundary = self.shape(boundary, &field("boundary"), depth)?;
+                let medium = self.medium(medium, &boundary.bounding_box(), &field("medium"))?;
+                let material = match material {
+                    Some(material) => Some(self.material(material, &field("material"), 0)?),
+                    None => None,
+                };
+                vec![HittableKind::Volume(Volume::new(
+                    boundary, medium, material,
+                ))]
+            }
+            ObjectDesc::Group { objects } => {
+                let mut list = HittableList { objects: vec![] };
+                for (i, object) in objects.iter().enumerate() {
+                    let key = format!("{}[{}]", field("objects"), i);
+                    list.objects.extend(self.object(object, &key, depth)?);
+                }
+                vec![HittableKind::Bvh(BvhNode::new(list, SplitStrategy::Sah))]
+            }
+        })
+    }
+
+    fn shape(
+        &self,
+        shape: &ShapeRef,
+        key: &str,
+        depth: usize,
+    ) -> Result<Arc<HittableKind>, SceneError> {
+        let (desc, key, name) = match shape {
+            ShapeRef::Inline(desc) => (&**desc, key.to_owned(), None),
+            ShapeRef::Named(name) => {
+                if let Some(shape) = self.built_shapes.borrow().get(name) {
+                    return Ok(shape.clone());
+                }
+                if depth >= MAX_SHAPE_DEPTH {
+                    return Err(self.invalid(key, format!("shape `{}` refers to itself", name)));
+                }
+                let desc = self
+                    .shapes
+                    .get(name)
+                    .ok_or_else(|| self.invalid(key, format!("unknown shape `{}`", name)))?;
+                (desc, format!("shapes.{}", name), Some(name))
+            }
+        };
+
+        let mut objects = self.object(desc, &key, depth + 1)?;
+        let shape = Arc::new(if objects.len() == 1 {
+            objects.remove(0)
+        } else {
+            HittableKind::Bvh(BvhNode::new(HittableList { objects }, SplitStrategy::Sah))
+        });
+        if let Some(name) = name {
+            self.built_shapes
+                .borrow_mut()
+                .insert(name.clone(), shape.clone());
+        }
+        Ok(shape)
+    }
+
+    fn medium(&self, medium: &MediumDesc, bounds: &Aabb, key: &str) -> Result<Medium, SceneError> {
+        let field = |name: &str| format!("{}.{}", key, name);
+        if medium.density < 0. {
+            return Err(self.invalid(field("density"), "must not be negative"));
+        }
+        if medium.g <= -1. || medium.g >= 1. {
+            return Err(self.invalid(field("g"), "must be between -1 and 1"));
+        }
+
+        let field = match &medium.field {
+            DensityFieldDesc::Uniform => DensityField::Uniform,
+            DensityFieldDesc::Grid {
+                path,
+                resolution,
+                min,
+                max,
+            } => {
+                let key = field("field.grid");
+                if resolution.contains(&0) {
+                    return Err(self.invalid(format!("{}.resolution", key), "must not be zero"));
+                }
+                let bounds = Aabb::from_points(
+                    min.map_or(
+                        Point::new(bounds.x.min, bounds.y.min, bounds.z.min),
+                        Point::from,
+                    ),
+                    max.map_or(
+                        Point::new(bounds.x.max, bounds.y.max, bounds.z.max),
+                        Point::from,
+                    ),
+                );
+                let valid = |axis| {
+                    let size = bounds.axis_interval(axis).size();
+                    size.is_finite() && size > 0.
+                };
+                if !(0..3).all(valid) {
+                    return Err(self.invalid(key, "must span a finite, non-empty box"));
+                }
+                let path = self.resolve(path);
+                let grid = VoxelGrid::load(&path, *resolution, bounds).map_err(|source| {
+                    self.invalid(
+                        format!("{}.path", key),
+                        format!("{}: {}", path.display(), source),
+                    )
+                })?;
+                DensityField::Grid(grid)
+            }
+            DensityFieldDesc::Noise {
+                seed,
+                scale,
+                fractal,
+            } => {
+                let key = field("field.noise");
+                DensityField::Noise {
+                    perlin: Arc::new(Perlin::new(*seed)),
+                    scale: self.noise_scale(*scale, &key)?,
+                    fractal: self.fractal(fractal, &key)?,
+                }
+            }
+        };
+
+        Ok(Medium {
+            field,
+            density: medium.density,
+            albedo: medium.albedo.into(),
+            g: medium.g,
+        })
+    }
+
+    fn transform(&self, steps: &[TransformDesc], key: &str) -> Result<Transform, SceneError> {
+        let mut total = Transform::IDENTITY;
+        for (i, step) in steps.iter().enumerate() {
+            total = self.transform_step(step, &format!("{}[{}]", key, i))? * total;
+        }
+        Ok(total)
+    }
+
+    fn transform_step(&self, step: &TransformDesc, key: &str) -> Result<Transform, SceneError> {
+        Ok(match step {
+            TransformDesc::Translate(offset) => Transform::translate((*offset).into()),
+            TransformDesc::Scale(factors) => {
+                if factors.contains(&0.) {
+                    return Err(self.invalid(format!("{}.scale", key), "must not be zero"));
+                }
+                Transform::scale((*factors).into())
+            }
+            TransformDesc::Rotate { axis, angle } => {
+                Transform::rotate(self.normal(*axis, &format!("{}.rotate.axis", key))?, *angle)
+            }
+            TransformDesc::Matrix(m) => {
+                let key = format!("{}.matrix", key);
+                if m[3] != [0., 0., 0., 1.] {
+                    return Err(self.invalid(key, "last row must be [0, 0, 0, 1]"));
+                }
+                Transform::new(*m).ok_or_else(|| self.invalid(key, "must be invertible"))?
+            }
+        })
+    }
+
+    fn normal(&self, normal: [f64; 3], key: &str) -> Result<Vec3, SceneError> {
+        let normal = Vec3::from(normal);
+        if normal.length_squared() == 0. {
+            return Err(self.invalid(key, "must not be zero"));
+        }
+        Ok(normal)
+    }
+
+    fn light(&self, light: &LightDesc, key: &str) -> Result<LightKind, SceneError> {
+        let field = |name: &str| format!("{}.{}", key, name);
+        Ok(match light {
+            LightDesc::Point {
+                position,
+                intensity,
+            } => LightKind::Point(PointLight::new((*position).into(), (*intensity).into())),
+            LightDesc::Spot {
+                position,
+                direction,
+                intensity,
+                inner_angle,
+                outer_angle,
+            } => {
+                if !(*outer_angle > 0. && *outer_angle < 180.) {
+                    return Err(self.invalid(field("outer_angle"), "must be between 0 and 180"));
+                }
+                let inner_angle = inner_angle.unwrap_or(*outer_angle);
+                if !(0. ..=*outer_angle).contains(&inner_angle) {
+                    return Err(
+                        self.invalid(field("inner_angle"), "must be between 0 and outer_angle")
+                    );
+                }
+                LightKind::Spot(SpotLight::new(
+                    (*position).into(),
+                    self.normal(*direction, &field("direction"))?,
+                    (*intensity).into(),
+                    inner_angle,
+                    *outer_angle,
+                ))
+            }
+            LightDesc::Directional {
+                direction,
+                intensity,
+                angular_diameter,
+            } => {
+                if !(0. ..180.).contains(angular_diameter) {
+                    return Err(self.invalid(
+                        field("angular_diameter"),
+                        "must be at least 0 and less than 180",
+                    ));
+                }
+                LightKind::Directional(DirectionalLight::new(
+                    self.normal(*direction, &field("direction"))?,
+                    (*intensity).into(),
+                    *angular_diameter,
+                ))
+            }
+        })
+    }
+
+    fn environment(&self, environment: &EnvironmentDesc) -> Result<Environment, SceneError> {
+        Ok(match environment {
+            EnvironmentDesc::Solid { color } => Environment::Solid((*color).into()),
+            EnvironmentDesc::Gradient { bottom, top } => Environment::Gradient {
+                bottom: (*bottom).into(),
+                top: (*top).into(),
+            },
+            EnvironmentDesc::Image {
+                path,
+                rotation,
+                intensity,
+            } => {
+                let path = self.resolve(path);
+                let map = EnvironmentMap::load(&path, *rotation, *intensity).map_err(|source| {
+                    self.invalid(
+                        "environment.image.path",
+                        format!("{}: {}", path.display(), source),
+                    )
+                })?;
+                Environment::Image(map)
+            }
+        })
+    }
+}
+
+pub struct Scene {
+    pub camera: CameraSettings,
+    pub world: HittableList,
+    pub lights: Vec<LightKind>,
+    pub environment: Environment,
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> Result<Scene, SceneError> {
+        let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        Scene::parse(path, &source)
+    }
+
+    // `path` locates errors and the files the scene refers to
+    fn parse(path: &Path, source: &str) -> Result<Scene, SceneError> {
+        let desc: SceneDesc = toml::from_str(source).map_err(|source| SceneError::Parse {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        let loader = Loader {
+            path,
+            textures: &desc.textures,
+            materials: &desc.materials,
+            shapes: &desc.shapes,
+            built_shapes: RefCell::new(HashMap::new()),
+            images: RefCell::new(HashMap::new()),
+        };
+
+        desc.camera
+            .validate()
+            .map_err(|(key, message)| loader.invalid(format!("camera.{}", key), message))?;
+
+        let mut world = HittableList { objects: vec![] };
+        for (i, object) in desc.objects.iter().enumerate() {
+            world
+                .objects
+                .extend(loader.object(object, &format!("objects[{}]", i), 0)?);
+        }
+
+        let environment = match &desc.environment {
+            Some(environment) => loader.environment(environment)?,
+            None => Environment::default(),
+        };
+
+        let lights = desc
+            .lights
+            .iter()
+            .enumerate()
+            .map(|(i, light)| loader.light(light, &format!("lights[{}]", i)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Scene {
+            camera: desc.camera,
+            world,
+            lights,
+            environment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = "
+        [camera]
+        aspect_ratio = 1.0
+        image_width = 100
+        samples_per_pixel = 1
+        max_depth = 1
+        vfov = 40
+        look_from = [0, 0, 1]
+        look_at = [0, 0, 0]
+        defocus_angle = 0
+    ";
+
+    fn invalid_key(scene: &str) -> (String, String) {
+        let source = format!("{}\n{}", CAMERA, scene);
+        match Scene::parse(Path::new("test.toml"), &source) {
+            Err(SceneError::Invalid { key, message, .. }) => (key, message),
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("loaded without an error"),
+        }
+    }
+
+    #[test]
+    fn errors_name_the_object_field() {
+        let (key, _) = invalid_key(
+            "
+            [materials.white.lambertian]
+            albedo = [0.5, 0.5, 0.5]
+
+            [[objects]]
+            sphere = { center = [0, 0, 0], radius = 1, material = \"white\" }
+
+            [[objects]]
+            sphere = { center = [0, 0, 0], radius = -1, material = \"white\" }
+            ",
+        );
+        assert_eq!(key, "objects[1].radius");
+    }
+
+    #[test]
+    fn errors_inside_named_materials_name_the_material() {
+        let (key, message) = invalid_key(
+            "
+            [materials.shiny.metal]
+            albedo = [0.8, 0.8, 0.8]
+            fuzz = -0.5
+
+            [[objects]]
+            sphere = { center = [0, 0, 0], radius = 1, material = \"shiny\" }
+            ",
+        );
+        assert_eq!(
+            (key.as_str(), message.as_str()),
+            ("materials.shiny.fuzz", "must not be negative")
+        );
+    }
+
+    #[test]
+    fn unknown_names_are_reported_where_they_are_used() {
+        let (key, message) = invalid_key(
+            "
+            [[objects]]
+            sphere = { center = [0, 0, 0], radius = 1, material = \"missing\" }
+            ",
+        );
+        assert_eq!(key, "objects[0].material");
+        assert_eq!(message, "unknown material `missing`");
+    }
+
+    #[test]
+    fn unreadable_files_name_their_key() {
+        let (key, _) = invalid_key(
+            "
+            [[objects]]
+            mesh = { path = \"does_not_exist.obj\" }
+            ",
+        );
+        assert_eq!(key, "objects[0].path");
+    }
+
+    #[test]
+    fn camera_errors_name_the_setting() {
+        let source = CAMERA.replace("image_width = 100", "image_width = 0");
+        match Scene::parse(Path::new("test.toml"), &source) {
+            Err(SceneError::Invalid { key, .. }) => assert_eq!(key, "camera.image_width"),
+            _ => panic!("expected an invalid camera"),
+        }
+    }
+}
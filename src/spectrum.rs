@@ -0,0 +1,62 @@
+use std::sync::LazyLock;
+
+use crate::vec3::{Color, Vec3};
+
+// visible range sampled in spectral mode, in nanometers
+const MIN_WAVELENGTH: f64 = 380.;
+const MAX_WAVELENGTH: f64 = 780.;
+
+// the d line of helium, where glass catalogs quote refraction indices
+pub const D_LINE: f64 = 587.56;
+
+// CIE 1931 color matching functions, the multi-lobe fit of Wyman, Sloan and
+// Shirley 2013
+pub fn cie_xyz(wavelength: f64) -> Vec3 {
+    let g = |mu: f64, sigma1: f64, sigma2: f64| {
+        let t = (wavelength - mu) / if wavelength < mu { sigma1 } else { sigma2 };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+// linear sRGB primaries, with negative components outside of the gamut
+fn xyz_to_rgb(xyz: Vec3) -> Color {
+    Color::new(
+        3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+        -0.9692660 * xyz.x() + 1.8760108 * xyz.y() + 0.0415560 * xyz.z(),
+        0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+    )
+}
+
+// the color of an equal energy spectrum over the sampled range, which has to
+// come out white
+static WHITE: LazyLock<Color> = LazyLock::new(|| {
+    let mut white = Color::BLACK;
+    let mut wavelength = MIN_WAVELENGTH + 0.5;
+    while wavelength < MAX_WAVELENGTH {
+        white += xyz_to_rgb(cie_xyz(wavelength));
+        wavelength += 1.;
+    }
+    white
+});
+
+// maps `u` in [0, 1) to a wavelength, returned with the color a path traced at
+// it contributes per unit of radiance. Scene colors act as filters that pass
+// the same fraction of every wavelength in their band, so the weights of all
+// wavelengths average out to white and only dispersion separates them.
+pub fn sample_wavelength(u: f64) -> (f64, Color) {
+    let wavelength = MIN_WAVELENGTH + u * (MAX_WAVELENGTH - MIN_WAVELENGTH);
+    let rgb = xyz_to_rgb(cie_xyz(wavelength));
+    let white = *WHITE;
+    let scale = MAX_WAVELENGTH - MIN_WAVELENGTH;
+    let weight = Color::new(
+        rgb.x() / white.x(),
+        rgb.y() / white.y(),
+        rgb.z() / white.z(),
+    );
+    (wavelength, scale * weight)
+}
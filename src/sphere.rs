@@ -1,30 +1,89 @@
+use std::f64::consts::PI;
+
 use crate::{
+    aabb::Aabb,
     hittable::{HitRecord, Hittable},
     interval::Interval,
     material::MaterialKind,
+    onb::Onb,
+    random::random_f64,
     ray::Ray,
-    vec3::Point,
+    vec3::{Point, Vec3},
 };
 
+// moving spheres travel from `center` at `time0` to `center + motion` at
+// `time1` and rest at either end outside of that
+#[derive(Clone)]
 pub struct Sphere {
     pub center: Point,
+    pub motion: Vec3,
+    pub time0: f64,
+    pub time1: f64,
     pub radius: f64,
     pub material: MaterialKind,
+    bbox: Aabb,
 }
 
 impl Sphere {
     pub fn new(center: Point, radius: f64, material: MaterialKind) -> Sphere {
-        return Sphere {
-            center,
+        Sphere::moving(center, center, 0., 1., radius, material)
+    }
+
+    pub fn moving(
+        center0: Point,
+        center1: Point,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: MaterialKind,
+    ) -> Sphere {
+        let rvec = Vec3::new(radius, radius, radius);
+        Sphere {
+            center: center0,
+            motion: center1 - center0,
+            time0,
+            time1,
             radius,
             material,
-        };
+            bbox: Aabb::surrounding(
+                Aabb::from_points(center0 - rvec, center0 + rvec),
+                Aabb::from_points(center1 - rvec, center1 + rvec),
+            ),
+        }
+    }
+
+    // an empty time interval keeps the sphere at its start
+    fn center_at(&self, time: f64) -> Point {
+        if self.time1 <= self.time0 {
+            return self.center;
+        }
+        let s = (time - self.time0) / (self.time1 - self.time0);
+        self.center + s.clamp(0., 1.) * self.motion
+    }
+
+    // 1 - cos_theta_max of the cone the sphere subtends from a distance,
+    // without the cancellation that makes far away spheres vanish
+    fn cone_height(&self, distance_squared: f64) -> f64 {
+        let x = self.radius * self.radius / distance_squared;
+        x / (1. + (1. - x).sqrt())
+    }
+}
+
+impl Sphere {
+    // maps a point on the unit sphere to u in [0, 1] around the y axis
+    // starting from -x, and v in [0, 1] from y = -1 to y = 1
+    fn uv(p: Vec3) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+
+        (phi / (2. * PI), theta / PI)
     }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let oc = self.center - ray.origin;
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let center = self.center_at(ray.time);
+        let oc = center - ray.origin;
         let a = ray.dir.length_squared();
         let h = ray.dir.dot(oc);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -45,14 +104,57 @@ impl Hittable for Sphere {
 
         let t = root;
         let point = ray.at(t);
-        let outward_normal = (point - self.center) / self.radius;
+        let outward_normal = (point - center) / self.radius;
 
-        Some(HitRecord::new(
+        let hit_rec = HitRecord::new(
             ray,
             &point,
             t,
             outward_normal,
-            self.material,
-        ))
+            Sphere::uv(outward_normal),
+            &self.material,
+        );
+
+        // derivatives of the point over u = phi / 2pi and v = theta / pi,
+        // which vanish at the poles
+        let (x, y, z) = (outward_normal.x(), outward_normal.y(), outward_normal.z());
+        let sin_theta = (x * x + z * z).sqrt();
+        if sin_theta == 0. {
+            return Some(hit_rec);
+        }
+        let dp_dphi = self.radius * Vec3::new(z, 0., -x);
+        let dp_dtheta = self.radius * Vec3::new(-x * y / sin_theta, sin_theta, -y * z / sin_theta);
+        Some(hit_rec.with_tangents(2. * PI * dp_dphi, PI * dp_dtheta))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // directions are spread uniformly over the cone the sphere subtends, from
+    // inside there is no cone and the sphere is left to scattered rays
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        if self.hit(ray, Interval::new(0.001, f64::INFINITY)).is_none() {
+            return 0.;
+        }
+
+        let distance_squared = (self.center_at(ray.time) - ray.origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 0.;
+        }
+        1. / (2. * PI * self.cone_height(distance_squared))
+    }
+
+    fn random(&self, origin: Point, time: f64) -> Vec3 {
+        let direction = self.center_at(time) - origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return Vec3::random_unit_vector();
+        }
+
+        let z = 1. - random_f64() * self.cone_height(distance_squared);
+        let phi = 2. * PI * random_f64();
+        let r = (1. - z * z).sqrt();
+        Onb::new(direction).transform(Vec3::new(phi.cos() * r, phi.sin() * r, z))
     }
 }
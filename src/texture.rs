@@ -0,0 +1,384 @@
+use std::{path::Path, sync::Arc};
+
+use image::DynamicImage;
+use serde::Deserialize;
+
+use crate::{
+    noise::{Fractal, Perlin},
+    vec3::{srgb_to_linear, Color, Point},
+};
+
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color;
+}
+
+#[derive(Clone, Debug)]
+pub enum TextureKind {
+    Solid(Color),
+    Checker(Checker),
+    UvChecker(UvChecker),
+    Image(ImageTexture),
+    Noise(Noise),
+    Marble(Marble),
+    Wood(Wood),
+    Worley(Worley),
+}
+
+impl Texture for TextureKind {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        match self {
+            TextureKind::Solid(color) => *color,
+            TextureKind::Checker(c) => c.value(u, v, p),
+            TextureKind::UvChecker(c) => c.value(u, v, p),
+            TextureKind::Image(i) => i.value(u, v, p),
+            TextureKind::Noise(n) => n.value(u, v, p),
+            TextureKind::Marble(m) => m.value(u, v, p),
+            TextureKind::Wood(w) => w.value(u, v, p),
+            TextureKind::Worley(w) => w.value(u, v, p),
+        }
+    }
+}
+
+impl From<Color> for TextureKind {
+    fn from(color: Color) -> Self {
+        TextureKind::Solid(color)
+    }
+}
+
+// alternates between two textures on a 3D grid of `scale` sized cells
+#[derive(Clone, Debug)]
+pub struct Checker {
+    inv_scale: f64,
+    even: Box<TextureKind>,
+    odd: Box<TextureKind>,
+}
+
+impl Checker {
+    pub fn new(scale: f64, even: TextureKind, odd: TextureKind) -> Checker {
+        Checker {
+            inv_scale: 1. / scale,
+            even: Box::new(even),
+            odd: Box::new(odd),
+        }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let x = (self.inv_scale * p.x()).floor() as i64;
+        let y = (self.inv_scale * p.y()).floor() as i64;
+        let z = (self.inv_scale * p.z()).floor() as i64;
+
+        if (x + y + z) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// alternates between two textures on a grid of `columns` by `rows` cells in
+// texture space
+#[derive(Clone, Debug)]
+pub struct UvChecker {
+    columns: f64,
+    rows: f64,
+    even: Box<TextureKind>,
+    odd: Box<TextureKind>,
+}
+
+impl UvChecker {
+    pub fn new(columns: f64, rows: f64, even: TextureKind, odd: TextureKind) -> UvChecker {
+        UvChecker {
+            columns,
+            rows,
+            even: Box::new(even),
+            odd: Box::new(odd),
+        }
+    }
+}
+
+impl Texture for UvChecker {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let x = (u * self.columns).floor() as i64;
+        let y = (v * self.rows).floor() as i64;
+
+        if (x + y) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Hash, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wrap {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Wrap {
+    fn apply(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(n),
+            Wrap::Clamp => i.clamp(0, n - 1),
+            Wrap::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m >= n {
+                    2 * n - 1 - m
+                } else {
+                    m
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+// pixels are shared between clones, so a texture can back many materials
+#[derive(Clone, Debug)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Arc<[Color]>,
+    pub filter: Filter,
+    pub wrap_u: Wrap,
+    pub wrap_v: Wrap,
+}
+
+impl ImageTexture {
+    // 8 and 16 bit images are assumed to be sRGB encoded and float images
+    // linear, unless the color space is given
+    pub fn load(path: &Path, color_space: Option<ColorSpace>) -> image::ImageResult<ImageTexture> {
+        let image = image::open(path)?;
+        let color_space = color_space.unwrap_or(match image {
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => ColorSpace::Linear,
+            _ => ColorSpace::Srgb,
+        });
+
+        let image = image.into_rgb32f();
+        let decode = |c: f32| match color_space {
+            ColorSpace::Linear => c as f64,
+            ColorSpace::Srgb => srgb_to_linear(c as f64),
+        };
+        let pixels = image
+            .pixels()
+            .map(|p| Color::new(decode(p[0]), decode(p[1]), decode(p[2])))
+            .collect();
+
+        Ok(ImageTexture {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            pixels,
+            filter: Filter::default(),
+            wrap_u: Wrap::default(),
+            wrap_v: Wrap::default(),
+        })
+    }
+
+    fn texel(&self, i: i64, j: i64) -> Color {
+        let i = self.wrap_u.apply(i, self.width);
+        let j = self.wrap_v.apply(j, self.height);
+        self.pixels[j * self.width + i]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _: &Point) -> Color {
+        if self.width == 0 || self.height == 0 {
+            return Color::new(0., 1., 1.);
+        }
+
+        // v runs bottom to top while image rows run top to bottom
+        let x = u * self.width as f64;
+        let y = (1. - v) * self.height as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (i, j) = (x0 as i64, y0 as i64);
+
+                let top = (1. - tx) * self.texel(i, j) + tx * self.texel(i + 1, j);
+                let bottom = (1. - tx) * self.texel(i, j + 1) + tx * self.texel(i + 1, j + 1);
+                (1. - ty) * top + ty * bottom
+            }
+        }
+    }
+}
+
+// the procedural textures below map a pattern in [0, 1] onto a blend between
+// two textures, `scale` is the frequency of the noise in world space
+fn blend(low: &TextureKind, high: &TextureKind, t: f64, u: f64, v: f64, p: &Point) -> Color {
+    let t = t.clamp(0., 1.);
+    (1. - t) * low.value(u, v, p) + t * high.value(u, v, p)
+}
+
+#[derive(Clone, Debug)]
+pub struct Noise {
+    perlin: Arc<Perlin>,
+    scale: f64,
+    fractal: Fractal,
+    turbulence: bool,
+    low: Box<TextureKind>,
+    high: Box<TextureKind>,
+}
+
+impl Noise {
+    pub fn new(
+        seed: u64,
+        scale: f64,
+        fractal: Fractal,
+        turbulence: bool,
+        low: TextureKind,
+        high: TextureKind,
+    ) -> Noise {
+        Noise {
+            perlin: Arc::new(Perlin::new(seed)),
+            scale,
+            fractal,
+            turbulence,
+            low: Box::new(low),
+            high: Box::new(high),
+        }
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let q = self.scale * *p;
+        let t = if self.turbulence {
+            self.perlin.turbulence(&q, &self.fractal)
+        } else {
+            0.5 * (1. + self.perlin.fbm(&q, &self.fractal))
+        };
+        blend(&self.low, &self.high, t, u, v, p)
+    }
+}
+
+// veins running across z, displaced by turbulence
+#[derive(Clone, Debug)]
+pub struct Marble {
+    perlin: Arc<Perlin>,
+    scale: f64,
+    fractal: Fractal,
+    strength: f64,
+    low: Box<TextureKind>,
+    high: Box<TextureKind>,
+}
+
+impl Marble {
+    pub fn new(
+        seed: u64,
+        scale: f64,
+        fractal: Fractal,
+        strength: f64,
+        low: TextureKind,
+        high: TextureKind,
+    ) -> Marble {
+        Marble {
+            perlin: Arc::new(Perlin::new(seed)),
+            scale,
+            fractal,
+            strength,
+            low: Box::new(low),
+            high: Box::new(high),
+        }
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let q = self.scale * *p;
+        let phase = q.z() + self.strength * self.perlin.turbulence(&q, &self.fractal);
+        blend(&self.low, &self.high, 0.5 * (1. + phase.sin()), u, v, p)
+    }
+}
+
+// rings around the y axis, displaced by fBm
+#[derive(Clone, Debug)]
+pub struct Wood {
+    perlin: Arc<Perlin>,
+    scale: f64,
+    fractal: Fractal,
+    strength: f64,
+    low: Box<TextureKind>,
+    high: Box<TextureKind>,
+}
+
+impl Wood {
+    pub fn new(
+        seed: u64,
+        scale: f64,
+        fractal: Fractal,
+        strength: f64,
+        low: TextureKind,
+        high: TextureKind,
+    ) -> Wood {
+        Wood {
+            perlin: Arc::new(Perlin::new(seed)),
+            scale,
+            fractal,
+            strength,
+            low: Box::new(low),
+            high: Box::new(high),
+        }
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let q = self.scale * *p;
+        let radius = (q.x() * q.x() + q.z() * q.z()).sqrt();
+        let rings = radius + self.strength * self.perlin.fbm(&q, &self.fractal);
+        blend(&self.low, &self.high, rings.rem_euclid(1.), u, v, p)
+    }
+}
+
+// distance to the nearest of randomly scattered cell points
+#[derive(Clone, Debug)]
+pub struct Worley {
+    perlin: Arc<Perlin>,
+    scale: f64,
+    low: Box<TextureKind>,
+    high: Box<TextureKind>,
+}
+
+impl Worley {
+    pub fn new(seed: u64, scale: f64, low: TextureKind, high: TextureKind) -> Worley {
+        Worley {
+            perlin: Arc::new(Perlin::new(seed)),
+            scale,
+            low: Box::new(low),
+            high: Box::new(high),
+        }
+    }
+}
+
+impl Texture for Worley {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let t = self.perlin.worley(&(self.scale * *p));
+        blend(&self.low, &self.high, t, u, v, p)
+    }
+}
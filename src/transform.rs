@@ -0,0 +1,559 @@
+use std::ops;
+
+use crate::{
+    aabb::Aabb,
+    interval::Interval,
+    vec3::{Point, Vec3},
+};
+
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1., 0., 0., 0.],
+    [0., 1., 0., 0.],
+    [0., 0., 1., 0.],
+    [0., 0., 0., 1.],
+];
+
+// affine 4x4 transform, row-major and applied to column vectors; the inverse
+// is kept alongside so neither direction ever has to be solved for
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    m: Matrix,
+    inv: Matrix,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        m: IDENTITY,
+        inv: IDENTITY,
+    };
+
+    // None for singular matrices
+    pub fn new(m: Matrix) -> Option<Transform> {
+        Some(Transform { m, inv: invert(m)? })
+    }
+
+    pub fn translate(offset: Vec3) -> Transform {
+        let (x, y, z) = (offset.x(), offset.y(), offset.z());
+        Transform {
+            m: [
+                [1., 0., 0., x],
+                [0., 1., 0., y],
+                [0., 0., 1., z],
+                [0., 0., 0., 1.],
+            ],
+            inv: [
+                [1., 0., 0., -x],
+                [0., 1., 0., -y],
+                [0., 0., 1., -z],
+                [0., 0., 0., 1.],
+            ],
+        }
+    }
+
+    pub fn scale(factors: Vec3) -> Transform {
+        let (x, y, z) = (factors.x(), factors.y(), factors.z());
+        Transform {
+            m: [
+                [x, 0., 0., 0.],
+                [0., y, 0., 0.],
+                [0., 0., z, 0.],
+                [0., 0., 0., 1.],
+            ],
+            inv: [
+                [1. / x, 0., 0., 0.],
+                [0., 1. / y, 0., 0.],
+                [0., 0., 1. / z, 0.],
+                [0., 0., 0., 1.],
+            ],
+        }
+    }
+
+    // counterclockwise when looking down `axis` towards the origin
+    pub fn rotate(axis: Vec3, degrees: f64) -> Transform {
+        let a = axis.normalized();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let c = 1. - cos;
+
+        let m = [
+            [
+                cos + x * x * c,
+                x * y * c - z * sin,
+                x * z * c + y * sin,
+                0.,
+            ],
+            [
+                y * x * c + z * sin,
+                cos + y * y * c,
+                y * z * c - x * sin,
+                0.,
+            ],
+            [
+                z * x * c - y * sin,
+                z * y * c + x * sin,
+                cos + z * z * c,
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ];
+        Transform {
+            m,
+            inv: transpose(m),
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            m: self.inv,
+            inv: self.m,
+        }
+    }
+
+    // normals go through the inverse transpose so they stay perpendicular to
+    // sheared and non-uniformly scaled surfaces
+    pub fn normal_matrix(&self) -> [[f64; 3]; 3] {
+        let inv = self.inv;
+        [0, 1, 2].map(|i| [0, 1, 2].map(|j| inv[j][i]))
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        let m = &self.m;
+        let row = |i: usize| m[i][0] * p.x() + m[i][1] * p.y() + m[i][2] * p.z() + m[i][3];
+        Point::new(row(0), row(1), row(2))
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        let row = |i: usize| m[i][0] * v.x() + m[i][1] * v.y() + m[i][2] * v.z();
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    // not normalized
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let m = self.normal_matrix();
+        let row = |i: usize| m[i][0] * n.x() + m[i][1] * n.y() + m[i][2] * n.z();
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    // bounds of the transformed corners, unbounded boxes stay unbounded
+    pub fn bounding_box(&self, bbox: Aabb) -> Aabb {
+        if !bbox.surface_area().is_finite() {
+            return Aabb::new(Interval::UNIVERSE, Interval::UNIVERSE, Interval::UNIVERSE);
+        }
+
+        let (x, y, z) = (bbox.x, bbox.y, bbox.z);
+        let mut result = Aabb::EMPTY;
+        for cx in [x.min, x.max] {
+            for cy in [y.min, y.max] {
+                for cz in [z.min, z.max] {
+                    let corner = self.point(Point::new(cx, cy, cz));
+                    result = Aabb::surrounding(result, Aabb::from_points(corner, corner));
+                }
+            }
+        }
+        result
+    }
+}
+
+// `a * b` applies b first, then a
+impl ops::Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform {
+            m: multiply(&self.m, &rhs.m),
+            inv: multiply(&rhs.inv, &self.inv),
+        }
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.; 4]; 4];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    result
+}
+
+fn transpose(m: Matrix) -> Matrix {
+    [0, 1, 2, 3].map(|i| [0, 1, 2, 3].map(|j| m[j][i]))
+}
+
+// Gauss-Jordan elimination with partial pivoting
+fn invert(m: Matrix) -> Option<Matrix> {
+    let mut a = m;
+    let mut inv = IDENTITY;
+
+    for col in 0..4 {
+        let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        inv.swap(col, pivot);
+
+        let scale = 1. / a[col][col];
+        for j in 0..4 {
+            a[col][j] *= scale;
+            inv[col][j] *= scale;
+        }
+
+        for row in 0..4 {
+            if row == col {
+                continue;
+            }
+            let factor = a[row][col];
+            for j in 0..4 {
+                a[row][j] -= factor * a[col][j];
+                inv[row][j] -= factor * inv[col][j];
+            }
+        }
+    }
+
+    Some(inv)
+}
+
+type Matrix3 = [[f64; 3]; 3];
+
+// an affine transform split into translation, rotation and the remaining
+// scale and shear, the parts that can be interpolated independently
+#[derive(Copy, Clone, Debug)]
+struct Decomposed {
+    translation: Vec3,
+    rotation: [f64; 4],
+    stretch: Matrix3,
+}
+
+impl Decomposed {
+    // polar decomposition of the upper 3x3 by averaging with its inverse
+    // transpose until it converges to the closest rotation (Shoemake and
+    // Duff, "Matrix Animation and Polar Decomposition")
+    fn new(t: &Transform) -> Decomposed {
+        let m = t.m;
+        let translation = Vec3::new(m[0][3], m[1][3], m[2][3]);
+        let linear: Matrix3 = [0, 1, 2].map(|i| [0, 1, 2].map(|j| m[i][j]));
+
+        // mirrored transforms keep a proper rotation and a negative stretch
+        let sign = determinant3(&linear).signum();
+        let mut rotation = linear.map(|row| row.map(|c| sign * c));
+        for _ in 0..100 {
+            let Some(inv) = invert3(&rotation) else {
+                break;
+            };
+            let mut next = rotation;
+            let mut change: f64 = 0.;
+            for i in 0..3 {
+                for j in 0..3 {
+                    next[i][j] = 0.5 * (rotation[i][j] + inv[j][i]);
+                    change = change.max((next[i][j] - rotation[i][j]).abs());
+                }
+            }
+            rotation = next;
+            if change < 1e-12 {
+                break;
+            }
+        }
+
+        let stretch = multiply3(&transpose3(&rotation), &linear);
+        Decomposed {
+            translation,
+            rotation: quaternion(&rotation),
+            stretch,
+        }
+    }
+
+    fn lerp(&self, other: &Decomposed, t: f64) -> Transform {
+        let translation = (1. - t) * self.translation + t * other.translation;
+        let rotation = rotation_matrix(slerp(self.rotation, other.rotation, t));
+        let mut stretch = self.stretch;
+        for (i, row) in stretch.iter_mut().enumerate() {
+            for (j, c) in row.iter_mut().enumerate() {
+                *c = (1. - t) * *c + t * other.stretch[i][j];
+            }
+        }
+
+        let linear = multiply3(&rotation, &stretch);
+        let mut m = IDENTITY;
+        for i in 0..3 {
+            m[i][..3].copy_from_slice(&linear[i]);
+        }
+        m[0][3] = translation.x();
+        m[1][3] = translation.y();
+        m[2][3] = translation.z();
+        Transform::new(m).unwrap_or(Transform::IDENTITY)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Keyframe {
+    time: f64,
+    transform: Transform,
+    parts: Decomposed,
+}
+
+// transform moving through keyframes, held at the first and last one outside
+// of their times
+#[derive(Clone, Debug)]
+pub struct AnimatedTransform {
+    keys: Vec<Keyframe>,
+}
+
+impl From<Transform> for AnimatedTransform {
+    fn from(transform: Transform) -> Self {
+        AnimatedTransform::new(vec![(0., transform)])
+    }
+}
+
+// steps per keyframe segment when bounding the swept box
+const BOUND_STEPS: usize = 64;
+
+impl AnimatedTransform {
+    // `keys` must not be empty
+    pub fn new(mut keys: Vec<(f64, Transform)>) -> AnimatedTransform {
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        AnimatedTransform {
+            keys: keys
+                .into_iter()
+                .map(|(time, transform)| Keyframe {
+                    time,
+                    transform,
+                    parts: Decomposed::new(&transform),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        let first = &self.keys[0];
+        let last = &self.keys[self.keys.len() - 1];
+        if time <= first.time {
+            return first.transform;
+        }
+        if time >= last.time {
+            return last.transform;
+        }
+
+        let next = self.keys.partition_point(|key| key.time <= time);
+        let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        a.parts.lerp(&b.parts, t)
+    }
+
+    // covers every moment, not just the keyframes: each segment is sampled
+    // and the samples are padded by how far a corner can bulge out of the
+    // chord between two of them while rotating
+    pub fn bounding_box(&self, bbox: Aabb) -> Aabb {
+        let mut result = self.keys[0].transform.bounding_box(bbox);
+        if !bbox.surface_area().is_finite() {
+            return result;
+        }
+
+        let c = bbox.centroid();
+        let corner = Point::new(bbox.x.max, bbox.y.max, bbox.z.max) - c;
+        for pair in self.keys.windows(2) {
+            let (a, b) = (&pair[0].parts, &pair[1].parts);
+            let dot = a
+                .rotation
+                .iter()
+                .zip(b.rotation)
+                .map(|(p, q)| p * q)
+                .sum::<f64>();
+            let angle = 2. * dot.abs().min(1.).acos();
+            let step_angle = angle / BOUND_STEPS as f64;
+
+            for step in 0..=BOUND_STEPS {
+                let transform = a.lerp(b, step as f64 / BOUND_STEPS as f64);
+                let radius = transform.vector(corner).length()
+                    + (transform.point(c) - transform.point(Point::zero())).length();
+                let pad = radius * (1. - (0.5 * step_angle).cos());
+                let sample = transform.bounding_box(bbox);
+                let sample = Aabb::new(
+                    sample.x.expand(2. * pad),
+                    sample.y.expand(2. * pad),
+                    sample.z.expand(2. * pad),
+                );
+                result = Aabb::surrounding(result, sample);
+            }
+        }
+        result
+    }
+}
+
+fn multiply3(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn transpose3(m: &Matrix3) -> Matrix3 {
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| m[j][i]))
+}
+
+fn determinant3(m: &Matrix3) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+fn invert3(m: &Matrix3) -> Option<Matrix3> {
+    let det = determinant3(m);
+    if det.abs() < 1e-300 {
+        return None;
+    }
+    // adjugate over determinant, cofactors taken cyclically
+    Some([0, 1, 2].map(|i| {
+        [0, 1, 2].map(|j| {
+            let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+            let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+            (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / det
+        })
+    }))
+}
+
+// unit quaternion (w, x, y, z) of a rotation matrix
+fn quaternion(m: &Matrix3) -> [f64; 4] {
+    let trace = m[0][0] + m[1][1] + m[2][2];
+    let q = if trace > 0. {
+        let s = 2. * (trace + 1.).sqrt();
+        [
+            0.25 * s,
+            (m[2][1] - m[1][2]) / s,
+            (m[0][2] - m[2][0]) / s,
+            (m[1][0] - m[0][1]) / s,
+        ]
+    } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+        let s = 2. * (1. + m[0][0] - m[1][1] - m[2][2]).sqrt();
+        [
+            (m[2][1] - m[1][2]) / s,
+            0.25 * s,
+            (m[0][1] + m[1][0]) / s,
+            (m[0][2] + m[2][0]) / s,
+        ]
+    } else if m[1][1] > m[2][2] {
+        let s = 2. * (1. + m[1][1] - m[0][0] - m[2][2]).sqrt();
+        [
+            (m[0][2] - m[2][0]) / s,
+            (m[0][1] + m[1][0]) / s,
+            0.25 * s,
+            (m[1][2] + m[2][1]) / s,
+        ]
+    } else {
+        let s = 2. * (1. + m[2][2] - m[0][0] - m[1][1]).sqrt();
+        [
+            (m[1][0] - m[0][1]) / s,
+            (m[0][2] + m[2][0]) / s,
+            (m[1][2] + m[2][1]) / s,
+            0.25 * s,
+        ]
+    };
+    normalize4(q)
+}
+
+fn rotation_matrix([w, x, y, z]: [f64; 4]) -> Matrix3 {
+    [
+        [
+            1. - 2. * (y * y + z * z),
+            2. * (x * y - w * z),
+            2. * (x * z + w * y),
+        ],
+        [
+            2. * (x * y + w * z),
+            1. - 2. * (x * x + z * z),
+            2. * (y * z - w * x),
+        ],
+        [
+            2. * (x * z - w * y),
+            2. * (y * z + w * x),
+            1. - 2. * (x * x + y * y),
+        ],
+    ]
+}
+
+// along the shorter arc
+fn slerp(a: [f64; 4], b: [f64; 4], t: f64) -> [f64; 4] {
+    let mut dot: f64 = a.iter().zip(b).map(|(p, q)| p * q).sum();
+    let b = if dot < 0. {
+        dot = -dot;
+        b.map(|c| -c)
+    } else {
+        b
+    };
+
+    if dot > 0.9995 {
+        return normalize4([0, 1, 2, 3].map(|i| (1. - t) * a[i] + t * b[i]));
+    }
+    let theta = dot.acos();
+    let (wa, wb) = (
+        ((1. - t) * theta).sin() / theta.sin(),
+        (t * theta).sin() / theta.sin(),
+    );
+    [0, 1, 2, 3].map(|i| wa * a[i] + wb * b[i])
+}
+
+fn normalize4(q: [f64; 4]) -> [f64; 4] {
+    let len = q.iter().map(|c| c * c).sum::<f64>().sqrt();
+    q.map(|c| c / len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(m: &Matrix) {
+        for (i, row) in m.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                let expected = if i == j { 1. } else { 0. };
+                assert!((cell - expected).abs() < 1e-9, "{:?}", m);
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_times_matrix_is_identity() {
+        let m = [
+            [2., 1., 0., 3.],
+            [0., 0., 4., -1.],
+            [1., 3., 1., 2.],
+            [0., 0., 0., 1.],
+        ];
+        let t = Transform::new(m).unwrap();
+        assert_identity(&multiply(&t.inv, &t.m));
+        assert_identity(&multiply(&t.m, &t.inv));
+    }
+
+    #[test]
+    fn composed_inverses_match() {
+        let t = Transform::translate(Vec3::new(1., -2., 3.))
+            * Transform::rotate(Vec3::new(1., 1., 0.), 30.)
+            * Transform::scale(Vec3::new(2., 0.5, 3.));
+        assert_identity(&multiply(&t.inv, &t.m));
+        assert_identity(&multiply(&invert(t.m).unwrap(), &t.m));
+    }
+
+    #[test]
+    fn singular_matrices_have_no_transform() {
+        let mut m = IDENTITY;
+        m[2][2] = 0.;
+        assert!(Transform::new(m).is_none());
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_shear_and_scale() {
+        let shear = Transform::new([
+            [1., 2., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+        .unwrap();
+        let t = shear * Transform::scale(Vec3::new(3., 1., 0.5));
+
+        // a plane spanned by two tangents with the normal as their cross product
+        let (u, v) = (Vec3::new(1., 1., 0.), Vec3::new(0., 1., 1.));
+        let n = t.normal(u.cross(v));
+        assert!(n.dot(t.vector(u)).abs() < 1e-9);
+        assert!(n.dot(t.vector(v)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    bvh::{BvhNode, SplitStrategy},
+    hittable::{HitRecord, Hittable, HittableKind, HittableList},
+    interval::Interval,
+    material::MaterialKind,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+#[derive(Copy, Clone, Debug)]
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+// vertex buffers shared by every face, so a mesh carries a single material
+pub struct Mesh {
+    pub positions: Vec<Point>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<MeshFace>,
+    pub material: MaterialKind,
+}
+
+pub struct Triangle {
+    mesh: Arc<Mesh>,
+    face: usize,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    pub fn new(a: Point, b: Point, c: Point, material: MaterialKind) -> Triangle {
+        let mesh = Mesh {
+            positions: vec![a, b, c],
+            normals: vec![],
+            uvs: vec![],
+            faces: vec![MeshFace {
+                positions: [0, 1, 2],
+                normals: None,
+                uvs: None,
+            }],
+            material,
+        };
+        Triangle::from_mesh(Arc::new(mesh), 0)
+    }
+
+    pub fn from_mesh(mesh: Arc<Mesh>, face: usize) -> Triangle {
+        let [a, b, c] = mesh.faces[face].positions.map(|i| mesh.positions[i]);
+        let bbox = Aabb::surrounding(Aabb::from_points(a, b), Aabb::from_points(c, c));
+        Triangle { mesh, face, bbox }
+    }
+}
+
+impl Hittable for Triangle {
+    // watertight intersection from Woop, Benthin and Wald, "Watertight
+    // Ray/Triangle Intersection" (JCGT 2013): vertices are sheared into a
+    // space where the ray runs along +z, so shared edges are never missed
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let face = &self.mesh.faces[self.face];
+        let [p0, p1, p2] = face.positions.map(|i| self.mesh.positions[i]);
+
+        let dir = ray.dir;
+        let kz = if dir[0].abs() > dir[1].abs() {
+            if dir[0].abs() > dir[2].abs() {
+                0
+            } else {
+                2
+            }
+        } else if dir[1].abs() > dir[2].abs() {
+            1
+        } else {
+            2
+        };
+        let mut kx = (kz + 1) % 3;
+        let mut ky = (kx + 1) % 3;
+        if dir[kz] < 0. {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
+        let sx = dir[kx] / dir[kz];
+        let sy = dir[ky] / dir[kz];
+        let sz = 1. / dir[kz];
+
+        let a = p0 - ray.origin;
+        let b = p1 - ray.origin;
+        let c = p2 - ray.origin;
+
+        let ax = a[kx] - sx * a[kz];
+        let ay = a[ky] - sy * a[kz];
+        let bx = b[kx] - sx * b[kz];
+        let by = b[ky] - sy * b[kz];
+        let cx = c[kx] - sx * c[kz];
+        let cy = c[ky] - sy * c[kz];
+
+        let u = cx * by - cy * bx;
+        let v = ax * cy - ay * cx;
+        let w = bx * ay - by * ax;
+
+        if (u < 0. || v < 0. || w < 0.) && (u > 0. || v > 0. || w > 0.) {
+            return None;
+        }
+
+        let det = u + v + w;
+        if det == 0. {
+            return None;
+        }
+
+        let t_scaled = u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz];
+        let t = t_scaled / det;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let (b0, b1, b2) = (u / det, v / det, w / det);
+        let point = b0 * p0 + b1 * p1 + b2 * p2;
+
+        let geometric_normal = (p1 - p0).cross(p2 - p0).normalized();
+
+        // without texture coordinates the barycentrics of the second and
+        // third vertex are used instead
+        let [uv0, uv1, uv2] = match face.uvs {
+            Some(uvs) => uvs.map(|i| self.mesh.uvs[i]),
+            None => [(0., 0.), (1., 0.), (0., 1.)],
+        };
+        let uv = (
+            b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+        );
+
+        let mut hit_rec = HitRecord::new(ray, &point, t, geometric_normal, uv, &self.mesh.material);
+
+        // tangents from the change of texture coordinates along two edges
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let det = du02 * dv12 - dv02 * du12;
+        if det.abs() > 1e-12 {
+            let (dp02, dp12) = (p0 - p2, p1 - p2);
+            hit_rec = hit_rec.with_tangents(
+                (dv12 * dp02 - dv02 * dp12) / det,
+                (du02 * dp12 - du12 * dp02) / det,
+            );
+        }
+
+        if let Some(normals) = face.normals {
+            let [n0, n1, n2] = normals.map(|i| self.mesh.normals[i]);
+            let shading_normal = b0 * n0 + b1 * n1 + b2 * n2;
+            // vertex normals that cancel out keep the geometric normal
+            if !shading_normal.near_zero() && shading_normal.x().is_finite() {
+                let shading_normal = shading_normal.normalized();
+                hit_rec =
+                    hit_rec.with_shading_normal(if shading_normal.dot(geometric_normal) < 0. {
+                        -shading_normal
+                    } else {
+                        shading_normal
+                    });
+            }
+        }
+        Some(hit_rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+pub struct TriangleMesh {
+    bvh: BvhNode,
+}
+
+impl TriangleMesh {
+    pub fn new(mesh: Mesh) -> TriangleMesh {
+        let mesh = Arc::new(mesh);
+        let triangles = HittableList {
+            objects: (0..mesh.faces.len())
+                .map(|face| HittableKind::Triangle(Triangle::from_mesh(mesh.clone(), face)))
+                .collect(),
+        };
+
+        TriangleMesh {
+            bvh: BvhNode::new(triangles, SplitStrategy::Sah),
+        }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
@@ -39,10 +39,6 @@ impl Vec3 {
         self.length_squared().sqrt()
     }
 
-    pub fn to_ppm_row(&self) -> String {
-        format!("{} {} {}", self.x(), self.y(), self.z())
-    }
-
     pub fn dot(self, rhs: Vec3) -> f64 {
         self.x() * rhs.x() + self.y() * rhs.y() + self.z() * rhs.z()
     }
@@ -63,7 +59,7 @@ impl Vec3 {
 
     pub fn near_zero(self) -> bool {
         const S: f64 = 1e-8;
-        self.x() < S && self.y() < S && self.z() < S
+        self.x().abs() < S && self.y().abs() < S && self.z().abs() < S
     }
 
     pub fn reflect(self, n: Vec3) -> Vec3 {
@@ -126,6 +122,20 @@ impl Vec3 {
     }
 }
 
+impl From<[f64; 3]> for Vec3 {
+    fn from(e: [f64; 3]) -> Self {
+        Vec3 { e }
+    }
+}
+
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &f64 {
+        &self.e[i]
+    }
+}
+
 impl ops::Neg for Vec3 {
     type Output = Self;
 
@@ -162,7 +172,7 @@ impl ops::Sub for Vec3 {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        return Self::new(self.x() - rhs.x(), self.y() - rhs.y(), self.z() - rhs.z());
+        Self::new(self.x() - rhs.x(), self.y() - rhs.y(), self.z() - rhs.z())
     }
 }
 
@@ -210,41 +220,73 @@ impl ops::Div<f64> for Vec3 {
     type Output = Self;
 
     fn div(self, t: f64) -> Self::Output {
-        return Vec3 {
+        Vec3 {
             e: [
                 self.e[0] * (1. / t),
                 self.e[1] * (1. / t),
                 self.e[2] * (1. / t),
             ],
-        };
+        }
     }
 }
 
 impl Color {
     pub fn format(self) -> String {
-        let rbyte = (255. * Interval::INTENSITY.clamp(linear_to_gamma(self.x()))) as i32;
-        let gbyte = (255. * Interval::INTENSITY.clamp(linear_to_gamma(self.y()))) as i32;
-        let bbyte = (255. * Interval::INTENSITY.clamp(linear_to_gamma(self.z()))) as i32;
+        let rbyte = (255. * Interval::INTENSITY.clamp(linear_to_srgb(self.x()))) as i32;
+        let gbyte = (255. * Interval::INTENSITY.clamp(linear_to_srgb(self.y()))) as i32;
+        let bbyte = (255. * Interval::INTENSITY.clamp(linear_to_srgb(self.z()))) as i32;
 
         format!("{} {} {}\n", rbyte, gbyte, bbyte)
     }
 
-    pub fn to_rgb(&self) -> [i32; 3] {
-        let rbyte = (255. * Interval::INTENSITY.clamp(linear_to_gamma(self.x()))) as i32;
-        let gbyte = (255. * Interval::INTENSITY.clamp(linear_to_gamma(self.y()))) as i32;
-        let bbyte = (255. * Interval::INTENSITY.clamp(linear_to_gamma(self.z()))) as i32;
+    pub fn to_rgb(self) -> [i32; 3] {
+        let rbyte = (255. * Interval::INTENSITY.clamp(linear_to_srgb(self.x()))) as i32;
+        let gbyte = (255. * Interval::INTENSITY.clamp(linear_to_srgb(self.y()))) as i32;
+        let bbyte = (255. * Interval::INTENSITY.clamp(linear_to_srgb(self.z()))) as i32;
 
         [rbyte, gbyte, bbyte]
     }
 
+    // relative luminance of linear Rec. 709 primaries
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.x() + 0.7152 * self.y() + 0.0722 * self.z()
+    }
+
     pub const BLACK: Color = Color { e: [0., 0., 0.] };
     pub const WHITE: Color = Color { e: [1., 1., 1.] };
 }
 
-pub fn linear_to_gamma(linear_component: f64) -> f64 {
-    if linear_component > 0. {
-        linear_component.sqrt()
+// the sRGB transfer function, linear near black and a 2.4 power above
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+    if linear_component <= 0. {
+        0.
+    } else if linear_component <= 0.0031308 {
+        12.92 * linear_component
     } else {
+        1.055 * linear_component.powf(1. / 2.4) - 0.055
+    }
+}
+
+// the inverse of linear_to_srgb
+pub fn srgb_to_linear(srgb_component: f64) -> f64 {
+    if srgb_component <= 0. {
         0.
+    } else if srgb_component <= 0.04045 {
+        srgb_component / 12.92
+    } else {
+        ((srgb_component + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_decoding_inverts_the_output_encoding() {
+        for i in 0..=255 {
+            let srgb = i as f64 / 255.;
+            assert!((linear_to_srgb(srgb_to_linear(srgb)) - srgb).abs() < 1e-12);
+        }
     }
 }